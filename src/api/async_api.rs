@@ -0,0 +1,401 @@
+use futures::future::{self, Loop};
+use futures::{stream, Future, Stream};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::r#async::Client;
+use reqwest::{Method, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::timer::Delay;
+
+use super::transport::{self, HttpRequest, HttpResponse};
+use super::{
+  merge_requests, parse_response, retry, utils, GLApiError, GLApiResult, GetMergeRequestsQuery,
+  MergeRequest, Pagination, ReqParams,
+};
+
+/// Default count of requests sent at the same time
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+pub type GLFuture<T> = Box<dyn Future<Item = T, Error = failure::Error> + Send>;
+
+/// Asynchronous client of GitLab API, based on futures 0.1 and tokio 0.1 used by `reqwest`.
+/// Futures should be run on tokio runtime. Cloning is cheap, clones share the client
+/// and the request budget
+#[derive(Clone)]
+pub struct AsyncGLApi {
+  inner: Arc<Inner>,
+}
+
+struct Inner {
+  req_params: ReqParams,
+  client: Client,
+  requests_sent: Arc<AtomicU32>,
+  /// Set when `RateLimit-Remaining` of the last response is `0`
+  rate_limited_until: Mutex<Option<Instant>>,
+}
+
+impl AsyncGLApi {
+  pub fn init(req_params: ReqParams) -> GLApiResult<Self> {
+    AsyncGLApi::with_counter(req_params, Arc::new(AtomicU32::new(0)))
+  }
+
+  /// Creates client that shares count of sent requests with another client
+  pub(crate) fn with_counter(
+    req_params: ReqParams,
+    requests_sent: Arc<AtomicU32>,
+  ) -> GLApiResult<Self> {
+    let client = req_params.connection.build_async_client()?;
+    let inner = Inner {
+      req_params,
+      client,
+      requests_sent,
+      rate_limited_until: Mutex::new(None),
+    };
+    Ok(AsyncGLApi {
+      inner: Arc::new(inner),
+    })
+  }
+
+  pub fn req_params(&self) -> &ReqParams {
+    &self.inner.req_params
+  }
+
+  fn concurrency(&self) -> usize {
+    self
+      .inner
+      .req_params
+      .concurrency
+      .unwrap_or(DEFAULT_CONCURRENCY)
+      .max(1)
+  }
+
+  /// Builds URL of API endpoint with serialized query
+  fn url_with_query<Q>(&self, url_part: &str, query: Option<&Q>) -> GLApiResult<Url>
+  where
+    Q: Serialize + ?Sized,
+  {
+    utils::url_with_query(&self.inner.req_params.api_url(url_part)?, query)
+  }
+
+  fn spend_request_budget(&self) -> GLApiResult<()> {
+    let sent = self.inner.requests_sent.fetch_add(1, Ordering::SeqCst);
+    match self.inner.req_params.retry.budget() {
+      Some(budget) if sent >= budget => Err(GLApiError::RequestBudgetExceeded { budget }.into()),
+      _ => Ok(()),
+    }
+  }
+
+  /// Time when rate limit is reset if GitLab reported that it is exhausted.
+  /// Every request sent concurrently waits for it
+  fn rate_limit_wait(&self) -> Option<Instant> {
+    let until = (*self.inner.rate_limited_until.lock().ok()?)?;
+    let pause = until.checked_duration_since(Instant::now())?;
+    warn!(
+      "GitLab rate limit is exhausted, waiting {:.1}s",
+      pause.as_secs_f64()
+    );
+    Some(until)
+  }
+
+  fn set_rate_limited_until(&self, until: Option<Instant>) {
+    if let Ok(mut r) = self.inner.rate_limited_until.lock() {
+      *r = until;
+    }
+  }
+
+  fn send_once(
+    &self,
+    method: Method,
+    url: Url,
+    body: Option<Arc<Vec<u8>>>,
+  ) -> GLFuture<HttpResponse> {
+    let params = &self.inner.req_params;
+    let token = match params.get_private_token_checked() {
+      Ok(t) => t,
+      Err(e) => return Box::new(future::err(e)),
+    };
+    let (auth_name, auth_value) = match params.auth_header(token) {
+      Ok(h) => h,
+      Err(e) => return Box::new(future::err(e)),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(auth_name, auth_value);
+    if body.is_some() {
+      headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    let req = HttpRequest {
+      method: method.clone(),
+      url: url.to_string(),
+      headers,
+      body: body.map(|b| b.to_vec()),
+    };
+    transport::log_request(&req);
+
+    let mut builder = self
+      .inner
+      .client
+      .request(req.method, url.clone())
+      .headers(req.headers);
+    if let Some(b) = req.body {
+      builder = builder.body(b);
+    }
+
+    let api = self.clone();
+    let started = Instant::now();
+    let fut = builder.send().and_then(move |mut resp| {
+      let status = resp.status();
+      let headers = resp.headers().clone();
+      resp.text().map(move |body| {
+        let resp = HttpResponse {
+          status,
+          headers,
+          body,
+        };
+        transport::log_response(&method, url.as_str(), &resp, started.elapsed());
+        let pause = retry::rate_limit_pause(&resp.headers);
+        api.set_rate_limited_until(pause.map(|p| Instant::now() + p));
+        resp
+      })
+    });
+    Box::new(fut.from_err())
+  }
+
+  /// Sends request, retrying it on transient failures according to retry policy.
+  /// Waits before it while rate limit of GitLab is exhausted
+  fn execute(&self, method: Method, url: Url, body: Option<Vec<u8>>) -> GLFuture<HttpResponse> {
+    let api = self.clone();
+    let body = body.map(Arc::new);
+
+    let fut = future::loop_fn(0u32, move |attempt| -> GLFuture<Loop<HttpResponse, u32>> {
+      if let Err(e) = api.spend_request_budget() {
+        return Box::new(future::err(e));
+      }
+      let policy = api.inner.req_params.retry;
+      let method = method.clone();
+      let url_str = url.to_string();
+
+      let wait: GLFuture<()> = match api.rate_limit_wait() {
+        Some(until) => Box::new(Delay::new(until).from_err()),
+        None => Box::new(future::ok(())),
+      };
+      let send_api = api.clone();
+      let (send_method, send_url, send_body) = (method.clone(), url.clone(), body.clone());
+      let retry_api = api.clone();
+      let fut = wait
+        .and_then(move |_| send_api.send_once(send_method, send_url, send_body))
+        .then(move |res| -> GLFuture<Loop<HttpResponse, u32>> {
+          let (failure, delay) = match res {
+            Ok(resp) => {
+              if !retry::is_retryable_status(resp.status)
+                || !policy.can_retry(&method, &url_str, attempt)
+              {
+                return Box::new(future::ok(Loop::Break(resp)));
+              }
+              match policy.delay(attempt, Some(&resp.headers)) {
+                Some(delay) => (resp.status.to_string(), delay),
+                None => return Box::new(future::ok(Loop::Break(resp))),
+              }
+            }
+            Err(e) => {
+              info!("{} {} failed: {}", method, url_str, e);
+              if !retry::is_transient_error(&e) || !policy.can_retry(&method, &url_str, attempt) {
+                return Box::new(future::err(e));
+              }
+              match policy.delay(attempt, None) {
+                Some(delay) => (e.to_string(), delay),
+                None => return Box::new(future::err(e)),
+              }
+            }
+          };
+
+          // rate limit delay is already included
+          retry_api.set_rate_limited_until(None);
+          warn!(
+            "{} {} failed ({}), retrying in {:.1}s",
+            method,
+            url_str,
+            failure,
+            delay.as_secs_f64()
+          );
+          let wait = Delay::new(Instant::now() + delay)
+            .from_err()
+            .map(move |_| Loop::Continue(attempt + 1));
+          Box::new(wait)
+        });
+      Box::new(fut)
+    });
+    Box::new(fut)
+  }
+
+  fn get_page<T>(&self, url: Url) -> GLFuture<(Vec<T>, HeaderMap)>
+  where
+    T: DeserializeOwned + Send + 'static,
+  {
+    let fut = self.execute(Method::GET, url, None).and_then(|resp| {
+      let items = parse_response(resp.status, &resp.headers, resp.body)?;
+      Ok((items, resp.headers))
+    });
+    Box::new(fut)
+  }
+
+  /// Requests list of items from `url`, which already contains query.
+  /// When GitLab reports count of pages (`X-Total-Pages`), the rest of pages are requested
+  /// concurrently, at most `page_concurrency` at the same time. Otherwise pagination headers
+  /// are followed one by one
+  fn get_paged_url<T>(
+    &self,
+    url: Url,
+    pagination: Pagination,
+    page_concurrency: usize,
+  ) -> GLFuture<Vec<T>>
+  where
+    T: DeserializeOwned + Send + 'static,
+  {
+    let api = self.clone();
+    let first_page = pagination.first_page();
+    let first_url = pagination.page_url(&url, first_page);
+
+    let fut = self
+      .get_page(first_url)
+      .and_then(move |(items, headers)| -> GLFuture<Vec<T>> {
+        if items.is_empty() || !pagination.wants_more(items.len()) {
+          return Box::new(future::ok(truncated(items, &pagination)));
+        }
+
+        let total_pages = header(&headers, "x-total-pages").and_then(|p| p.parse().ok());
+        if let (Some(total), false) = (total_pages, pagination.is_keyset()) {
+          let last_page = pagination.last_page(total);
+          let fut = stream::iter_ok((first_page + 1)..=last_page)
+            .map(move |page| api.get_page::<T>(pagination.page_url(&url, page)))
+            .buffered(page_concurrency)
+            .fold(items, |mut all, (mut page_items, _)| {
+              all.append(&mut page_items);
+              Ok::<_, failure::Error>(all)
+            })
+            .map(move |items| truncated(items, &pagination));
+          return Box::new(fut);
+        }
+
+        let fut = future::loop_fn(
+          (items, headers),
+          move |(items, headers)| -> GLFuture<Loop<Vec<T>, _>> {
+            let next_url = match header(&headers, "link").and_then(utils::next_page_link) {
+              Some(link) => Url::parse(&link).ok(),
+              None => header(&headers, "x-next-page")
+                .and_then(|p| p.parse().ok())
+                .map(|next_page| pagination.page_url(&url, next_page)),
+            };
+            let next_url = match next_url {
+              Some(u) => u,
+              None => return Box::new(future::ok(Loop::Break(items))),
+            };
+            let fut = api
+              .get_page::<T>(next_url)
+              .map(move |(mut page_items, headers)| {
+                let is_empty_page = page_items.is_empty();
+                let mut items = items;
+                items.append(&mut page_items);
+                if is_empty_page || !pagination.wants_more(items.len()) {
+                  Loop::Break(items)
+                } else {
+                  Loop::Continue((items, headers))
+                }
+              });
+            Box::new(fut)
+          },
+        )
+        .map(move |items| truncated(items, &pagination));
+        Box::new(fut)
+      });
+    Box::new(fut)
+  }
+
+  pub fn get_paged<T, Q>(
+    &self,
+    url_part: &str,
+    query: Option<&Q>,
+    pagination: &Pagination,
+  ) -> GLFuture<Vec<T>>
+  where
+    T: DeserializeOwned + Send + 'static,
+    Q: Serialize + ?Sized,
+  {
+    match self.url_with_query(url_part, query) {
+      Ok(url) => self.get_paged_url(url, *pagination, self.concurrency()),
+      Err(e) => Box::new(future::err(e)),
+    }
+  }
+
+  pub fn get_project_merge_requests(
+    &self,
+    project_in: Option<&str>,
+    query: &GetMergeRequestsQuery,
+    pagination: &Pagination,
+  ) -> GLFuture<Vec<MergeRequest>> {
+    let project = match project_in {
+      Some(pr) => pr,
+      None => match self.inner.req_params.get_default_project_checked() {
+        Ok(pr) => pr,
+        Err(e) => return Box::new(future::err(e)),
+      },
+    };
+    self.get_paged(
+      &merge_requests::url_project_mr(project),
+      Some(query),
+      pagination,
+    )
+  }
+
+  /// Requests merge requests of several projects concurrently.
+  /// Pages of every project are requested one by one, so that count of
+  /// concurrent requests stays bounded
+  pub fn get_merge_requests_of_projects(
+    &self,
+    projects: &[&str],
+    query: &GetMergeRequestsQuery,
+    pagination: &Pagination,
+  ) -> GLFuture<Vec<(String, Vec<MergeRequest>)>> {
+    let mut urls = Vec::with_capacity(projects.len());
+    for project in projects {
+      match self.url_with_query(&merge_requests::url_project_mr(project), Some(query)) {
+        Ok(url) => urls.push(((*project).to_owned(), url)),
+        Err(e) => return Box::new(future::err(e)),
+      }
+    }
+
+    let api = self.clone();
+    let pagination = *pagination;
+    let fut = stream::iter_ok(urls)
+      .map(move |(project, url)| {
+        api
+          .get_paged_url(url, pagination, 1)
+          .map(move |mrs| (project, mrs))
+      })
+      .buffered(self.concurrency())
+      .collect();
+    Box::new(fut)
+  }
+}
+
+/// Runs future on a new tokio runtime and waits for its result
+pub fn block_on<T: Send + 'static>(fut: GLFuture<T>) -> GLApiResult<T> {
+  let mut runtime = tokio::runtime::Runtime::new()?;
+  let result = runtime.block_on(fut);
+  runtime.shutdown_now().wait().ok();
+  result
+}
+
+fn header<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+  headers
+    .get(name)
+    .and_then(|h| h.to_str().ok())
+    .map(str::trim)
+    .filter(|h| !h.is_empty())
+}
+
+fn truncated<T>(mut items: Vec<T>, pagination: &Pagination) -> Vec<T> {
+  pagination.truncate(&mut items);
+  items
+}
@@ -4,7 +4,11 @@ pub fn url_all(project: &str) -> String {
   format!("/projects/{}/repository/branches", utils::encode(project))
 }
 
-#[derive(Deserialize)]
+pub fn url_one(project: &str, branch: &str) -> String {
+  format!("{}/{}", url_all(project), utils::encode(branch))
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Commit {
   pub author_email: String,
   pub author_name: String,
@@ -29,10 +33,10 @@ pub struct Commit {
   //   ]
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Branch {
   pub name: String,
-  pub merge: Option<bool>,
+  pub merged: Option<bool>,
   pub protected: bool,
   pub default: bool,
   pub developers_can_push: bool,
@@ -54,14 +58,11 @@ pub struct Branch {
 #[derive(Default, Serialize)]
 pub struct GetBranchesQuery<'a> {
   search: Option<&'a str>,
-  per_page: u8,
 }
 
 impl<'a> GetBranchesQuery<'a> {
   pub fn new() -> Self {
-    let mut q = GetBranchesQuery::default();
-    q.per_page = 100;
-    q
+    GetBranchesQuery::default()
   }
   pub fn search(mut self, val: &'a str) -> Self {
     self.search = Some(val);
@@ -0,0 +1,172 @@
+use directories::BaseDirs;
+use reqwest::header::ETAG;
+use reqwest::Url;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::cassette::RecordedResponse;
+use super::{GLApiResult, HttpResponse};
+use crate::configs::{create_private_dir, write_private};
+
+/// How long project (e.g. its default branch) is used without revalidation
+pub const PROJECT_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long lists of users are used without revalidation
+pub const USERS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long info of the private token (scopes and expiry) is used without revalidation
+pub const TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Response saved in cache file
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+  url: String,
+  etag: Option<String>,
+  /// Unix time when response was received or revalidated last time
+  stored_at: u64,
+  response: RecordedResponse,
+}
+
+/// Cached response of GET request
+pub(crate) struct Cached {
+  pub response: HttpResponse,
+  pub etag: Option<String>,
+  /// TTL is not expired yet, so response is used without request
+  pub fresh: bool,
+}
+
+/// On-disk cache of responses of slow-changing resources: single projects, users
+/// and info of the current token. Responses are keyed by full URL (host, path and query)
+/// and by the token they are requested with. A fresh response is used without request,
+/// a stale one is revalidated with `If-None-Match`
+#[derive(Clone)]
+pub struct ResponseCache {
+  dir: PathBuf,
+  project_ttl: Duration,
+  users_ttl: Duration,
+  token_ttl: Duration,
+}
+
+impl ResponseCache {
+  pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+    ResponseCache {
+      dir: dir.into(),
+      project_ttl: PROJECT_TTL,
+      users_ttl: USERS_TTL,
+      token_ttl: TOKEN_TTL,
+    }
+  }
+
+  /// `mergereq` directory in cache dir of the user, e.g. `~/.cache/mergereq` for Linux
+  pub fn default_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|bd| bd.cache_dir().join("mergereq"))
+  }
+
+  pub fn project_ttl(mut self, val: Duration) -> Self {
+    self.project_ttl = val;
+    self
+  }
+
+  pub fn users_ttl(mut self, val: Duration) -> Self {
+    self.users_ttl = val;
+    self
+  }
+
+  pub fn token_ttl(mut self, val: Duration) -> Self {
+    self.token_ttl = val;
+    self
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// Removes all cached responses. Returns count of removed entries
+  pub fn clear(&self) -> GLApiResult<usize> {
+    let entries = match fs::read_dir(&self.dir) {
+      Ok(e) => e,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+      Err(e) => return Err(e.into()),
+    };
+    let mut removed = 0;
+    for entry in entries {
+      let path = entry?.path();
+      if path.extension().is_some_and(|ext| ext == "json") {
+        fs::remove_file(path)?;
+        removed += 1;
+      }
+    }
+    Ok(removed)
+  }
+
+  /// TTL of resource at `url`, `None` if it is not cached
+  pub(crate) fn ttl(&self, url: &Url) -> Option<Duration> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let api_pos = segments.iter().position(|s| *s == "api")?;
+    // skip `api` and version
+    match segments.get(api_pos + 2..)? {
+      ["projects", _] => Some(self.project_ttl),
+      ["users"] => Some(self.users_ttl),
+      ["personal_access_tokens", "self"] => Some(self.token_ttl),
+      _ => None,
+    }
+  }
+
+  /// Entries are kept per token, as another token may see other data or have no access to it
+  fn entry_path(&self, url: &Url, token: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.as_str().hash(&mut hasher);
+    token.hash(&mut hasher);
+    self.dir.join(format!("{:016x}.json", hasher.finish()))
+  }
+
+  /// Cached response of GET request to `url` sent with `token`. Unreadable entries are ignored
+  pub(crate) fn lookup(&self, url: &Url, token: &str) -> Option<Cached> {
+    let ttl = self.ttl(url)?;
+    let data = fs::read_to_string(self.entry_path(url, token)).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&data).ok()?;
+    // another URL with the same hash
+    if entry.url != url.as_str() {
+      return None;
+    }
+    let age = now_secs().saturating_sub(entry.stored_at);
+    Some(Cached {
+      response: entry.response.into_response().ok()?,
+      etag: entry.etag,
+      fresh: age < ttl.as_secs(),
+    })
+  }
+
+  /// Saves successful response of GET request to `url` sent with `token`,
+  /// if the resource is cached
+  pub(crate) fn store(&self, url: &Url, token: &str, resp: &HttpResponse) -> GLApiResult<()> {
+    if self.ttl(url).is_none() || !resp.status.is_success() {
+      return Ok(());
+    }
+    let etag = resp
+      .headers
+      .get(ETAG)
+      .and_then(|v| v.to_str().ok())
+      .map(str::to_owned);
+    let entry = CacheEntry {
+      url: url.to_string(),
+      etag,
+      stored_at: now_secs(),
+      response: RecordedResponse::from_response(resp),
+    };
+    // entries contain private data and their names are derived from the token
+    create_private_dir(&self.dir)?;
+    write_private(
+      self.entry_path(url, token),
+      serde_json::to_string(&entry)?.as_bytes(),
+    )
+  }
+}
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,207 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{StatusCode, Url};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::transport::{redacted_headers, HttpRequest, HttpResponse, Transport};
+use super::{GLApiError, GLApiResult};
+use crate::configs::write_private;
+
+/// Request/response pair saved in cassette
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Interaction {
+  pub request: RecordedRequest,
+  pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecordedRequest {
+  pub method: String,
+  pub url: String,
+  #[serde(default, deserialize_with = "header_values")]
+  pub headers: BTreeMap<String, Vec<String>>,
+  pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecordedResponse {
+  pub status: u16,
+  #[serde(default, deserialize_with = "header_values")]
+  pub headers: BTreeMap<String, Vec<String>>,
+  pub body: String,
+}
+
+impl RecordedResponse {
+  /// Response with secret headers redacted
+  pub(crate) fn from_response(resp: &HttpResponse) -> Self {
+    RecordedResponse {
+      status: resp.status.as_u16(),
+      headers: redacted_headers(&resp.headers),
+      body: resp.body.clone(),
+    }
+  }
+
+  pub(crate) fn into_response(self) -> GLApiResult<HttpResponse> {
+    let mut headers = HeaderMap::new();
+    for (name, values) in &self.headers {
+      let name = HeaderName::from_bytes(name.as_bytes())?;
+      for value in values {
+        headers.append(name.clone(), HeaderValue::from_str(value)?);
+      }
+    }
+    Ok(HttpResponse {
+      status: StatusCode::from_u16(self.status)?,
+      headers,
+      body: self.body,
+    })
+  }
+}
+
+/// JSON file with API traffic of a command
+#[derive(Serialize, Deserialize, Default)]
+pub struct Cassette {
+  pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+  pub fn load<P: AsRef<Path>>(path: P) -> GLApiResult<Self> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path).map_err(|e| {
+      GLApiError::ParseError(format!("cannot read cassette `{}`: {}", path.display(), e))
+    })?;
+    Ok(serde_json::from_str(&data)?)
+  }
+
+  /// Saves cassette readable only by its owner, as responses may contain private data
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> GLApiResult<()> {
+    write_private(path, serde_json::to_string_pretty(self)?.as_bytes())
+  }
+
+  /// Scheme and host of the recorded requests, e.g. `https://gitlab.example.com`
+  pub fn base_url(&self) -> Option<String> {
+    let url = Url::parse(&self.interactions.first()?.request.url).ok()?;
+    let mut base = format!("{}://{}", url.scheme(), url.host_str()?);
+    if let Some(port) = url.port() {
+      base.push_str(&format!(":{}", port));
+    }
+    Some(base)
+  }
+}
+
+/// Header values of cassette, where a single value may be saved as string
+/// like older versions did
+fn header_values<'de, D>(deserializer: D) -> Result<BTreeMap<String, Vec<String>>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Values {
+    One(String),
+    Many(Vec<String>),
+  }
+  let headers: BTreeMap<String, Values> = serde::Deserialize::deserialize(deserializer)?;
+  let headers = headers.into_iter().map(|(name, values)| {
+    let values = match values {
+      Values::One(v) => vec![v],
+      Values::Many(v) => v,
+    };
+    (name, values)
+  });
+  Ok(headers.collect())
+}
+
+/// Path and query of URL, so that cassette can be replayed against another host
+fn path_and_query(url: &str) -> String {
+  match Url::parse(url) {
+    Ok(u) => match u.query() {
+      Some(q) => format!("{}?{}", u.path(), q),
+      None => u.path().to_owned(),
+    },
+    Err(_) => url.to_owned(),
+  }
+}
+
+/// Passes requests to another transport and writes every request/response pair
+/// to cassette file. Tokens and cookies are redacted
+pub struct RecordingTransport<T> {
+  inner: T,
+  path: PathBuf,
+  cassette: RefCell<Cassette>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+  pub fn new<P: Into<PathBuf>>(inner: T, path: P) -> Self {
+    RecordingTransport {
+      inner,
+      path: path.into(),
+      cassette: RefCell::new(Cassette::default()),
+    }
+  }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+  fn send(&self, req: &HttpRequest) -> GLApiResult<HttpResponse> {
+    let resp = self.inner.send(req)?;
+
+    let interaction = Interaction {
+      request: RecordedRequest {
+        method: req.method.to_string(),
+        url: req.url.clone(),
+        headers: redacted_headers(&req.headers),
+        body: req
+          .body
+          .as_ref()
+          .map(|b| String::from_utf8_lossy(b).into_owned()),
+      },
+      response: RecordedResponse::from_response(&resp),
+    };
+    // cassette is saved after every request, so it is complete even if the command fails
+    let mut cassette = self.cassette.borrow_mut();
+    cassette.interactions.push(interaction);
+    cassette.save(&self.path)?;
+    Ok(resp)
+  }
+}
+
+/// Serves responses from cassette instead of sending requests.
+/// Request is matched by method, path and query with the first interaction not served yet
+pub struct ReplayTransport {
+  interactions: RefCell<Vec<Option<Interaction>>>,
+}
+
+impl ReplayTransport {
+  pub fn new(cassette: Cassette) -> Self {
+    ReplayTransport {
+      interactions: RefCell::new(cassette.interactions.into_iter().map(Some).collect()),
+    }
+  }
+}
+
+impl Transport for ReplayTransport {
+  fn send(&self, req: &HttpRequest) -> GLApiResult<HttpResponse> {
+    let target = path_and_query(&req.url);
+    let mut interactions = self.interactions.borrow_mut();
+    let found = interactions.iter_mut().find(|i| {
+      i.as_ref().is_some_and(|i| {
+        i.request.method == req.method.as_str() && path_and_query(&i.request.url) == target
+      })
+    });
+    let interaction = match found.and_then(Option::take) {
+      Some(i) => i,
+      None => {
+        return Err(
+          GLApiError::ReplayMismatch {
+            method: req.method.to_string(),
+            url: req.url.clone(),
+          }
+          .into(),
+        )
+      }
+    };
+
+    interaction.response.into_response()
+  }
+}
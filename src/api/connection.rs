@@ -0,0 +1,159 @@
+use reqwest::{Certificate, Identity, Proxy, Url};
+use std::fmt;
+use std::time::Duration;
+
+use super::{GLApiError, GLApiResult};
+
+const PEM_CERT_END: &str = "-----END CERTIFICATE-----";
+
+/// Settings of connection to GitLab: timeouts, proxy and TLS
+#[derive(Default, Clone)]
+pub struct ConnectionSettings {
+  /// Timeout of establishing connection
+  pub connect_timeout: Option<Duration>,
+  /// Timeout of the whole request. Default is 30 seconds
+  pub timeout: Option<Duration>,
+  /// URL of HTTP(S) proxy
+  pub proxy: Option<String>,
+  /// Comma separated hosts that are requested without proxy. `*` disables proxy
+  pub no_proxy: Option<String>,
+  /// Path of PEM file with additional root certificates
+  pub ca_cert: Option<String>,
+  /// Path of PKCS#12 (`.p12`, `.pfx`) file with client certificate and key
+  pub client_identity: Option<String>,
+  pub client_identity_password: Option<String>,
+  /// Disables TLS certificate validation. Dangerous
+  pub accept_invalid_certs: bool,
+}
+
+/// Applies settings to blocking or async `ClientBuilder`, which have the same methods
+macro_rules! configure_builder {
+  ($settings:expr, $builder:expr) => {{
+    let settings = $settings;
+    let mut builder = $builder;
+
+    if let Some(t) = settings.connect_timeout {
+      builder = builder.connect_timeout(t);
+    }
+    if let Some(t) = settings.timeout {
+      builder = builder.timeout(t);
+    }
+
+    if let Some(proxy) = settings.make_proxy()? {
+      builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &settings.ca_cert {
+      for cert in read_pem_certs(path)? {
+        builder = builder.add_root_certificate(cert);
+      }
+    }
+
+    if let Some(path) = &settings.client_identity {
+      let der = std::fs::read(path)?;
+      let password = settings
+        .client_identity_password
+        .as_deref()
+        .unwrap_or_default();
+      builder = builder.identity(Identity::from_pkcs12_der(&der, password)?);
+    }
+
+    if settings.accept_invalid_certs {
+      builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder.build()?)
+  }};
+}
+
+/// Proxy URL with its password hidden
+fn redacted_proxy(proxy: &str) -> String {
+  match Url::parse(proxy) {
+    Ok(mut url) if url.password().is_some() => {
+      let _ = url.set_password(Some("[REDACTED]"));
+      url.to_string()
+    }
+    _ => proxy.to_owned(),
+  }
+}
+
+/// Secrets (proxy password, password of client identity) are not shown, so it can be logged
+impl fmt::Debug for ConnectionSettings {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("ConnectionSettings")
+      .field("connect_timeout", &self.connect_timeout)
+      .field("timeout", &self.timeout)
+      .field("proxy", &self.proxy.as_deref().map(redacted_proxy))
+      .field("no_proxy", &self.no_proxy)
+      .field("ca_cert", &self.ca_cert)
+      .field("client_identity", &self.client_identity)
+      .field(
+        "client_identity_password",
+        &self.client_identity_password.as_ref().map(|_| "[REDACTED]"),
+      )
+      .field("accept_invalid_certs", &self.accept_invalid_certs)
+      .finish()
+  }
+}
+
+impl ConnectionSettings {
+  pub(crate) fn build_client(&self) -> GLApiResult<reqwest::Client> {
+    configure_builder!(self, reqwest::Client::builder())
+  }
+
+  #[cfg(feature = "async")]
+  pub(crate) fn build_async_client(&self) -> GLApiResult<reqwest::r#async::Client> {
+    configure_builder!(self, reqwest::r#async::Client::builder())
+  }
+
+  fn make_proxy(&self) -> GLApiResult<Option<Proxy>> {
+    let proxy = match &self.proxy {
+      Some(p) => p,
+      None => return Ok(None),
+    };
+    let proxy_url = Url::parse(proxy)
+      .map_err(|e| GLApiError::ParseError(format!("proxy URL `{}`: {}", proxy, e)))?;
+    let no_proxy: Vec<String> = self
+      .no_proxy
+      .as_deref()
+      .unwrap_or_default()
+      .split(',')
+      .map(|h| h.trim().trim_start_matches('.').to_lowercase())
+      .filter(|h| !h.is_empty())
+      .collect();
+    Ok(Some(Proxy::custom(move |url| {
+      let host = url.host_str().unwrap_or_default();
+      if bypass_proxy(&no_proxy, host) {
+        None
+      } else {
+        Some(proxy_url.clone())
+      }
+    })))
+  }
+}
+
+/// Whether host matches one of `no_proxy` entries: exact host, its parent domain or `*`
+fn bypass_proxy(no_proxy: &[String], host: &str) -> bool {
+  let host = host.to_lowercase();
+  no_proxy.iter().any(|entry| {
+    entry == "*"
+      || host == *entry
+      || (host.ends_with(entry.as_str()) && host[..host.len() - entry.len()].ends_with('.'))
+  })
+}
+
+/// Reads all certificates of PEM bundle
+fn read_pem_certs(path: &str) -> GLApiResult<Vec<Certificate>> {
+  let pem = std::fs::read_to_string(path)?;
+  let mut certs = Vec::new();
+  for block in pem.split_inclusive(PEM_CERT_END) {
+    if block.contains(PEM_CERT_END) {
+      certs.push(Certificate::from_pem(block.trim().as_bytes())?);
+    }
+  }
+  if certs.is_empty() {
+    let msg = format!("no certificates found in `{}`", path);
+    return Err(GLApiError::ParseError(msg).into());
+  }
+  Ok(certs)
+}
@@ -1,3 +1,7 @@
+#![allow(non_local_definitions)]
+
+use std::collections::BTreeMap;
+
 #[derive(Fail, Debug)]
 pub enum GLApiError {
   #[fail(display = "{}", 0)]
@@ -10,12 +14,79 @@ pub enum GLApiError {
   NoProject,
   #[fail(display = "cannot parse response: {}", resp_text)]
   CantParseResp { resp_text: String },
-  #[fail(display = "API Error: {}", error)]
-  APIErr { error: String },
   #[fail(display = "Parse error: {}", 0)]
   ParseError(String),
   #[fail(display = "Cannot read config file")]
   ReadCfgError,
+  #[fail(display = "Unauthorized: {}. Check your private token", message)]
+  Unauthorized { message: String, body: String },
+  #[fail(display = "Forbidden: {}", message)]
+  Forbidden { message: String, body: String },
+  #[fail(display = "Not found: {}", message)]
+  NotFound { message: String, body: String },
+  #[fail(display = "Conflict: {}", message)]
+  Conflict { message: String, body: String },
+  #[fail(display = "Rate limit exceeded: {}", message)]
+  RateLimited {
+    message: String,
+    /// Seconds to wait from `Retry-After` header
+    retry_after: Option<u64>,
+    body: String,
+  },
+  #[fail(display = "Server error ({}): {}", status, message)]
+  ServerError {
+    status: u16,
+    message: String,
+    body: String,
+  },
+  #[fail(display = "Validation failed: {}", message)]
+  Validation {
+    status: u16,
+    message: String,
+    /// Field errors from GitLab's `message` object
+    errors: BTreeMap<String, Vec<String>>,
+    body: String,
+  },
+  #[fail(display = "API Error ({}): {}", status, message)]
+  HttpStatus {
+    status: u16,
+    message: String,
+    body: String,
+  },
+  #[fail(
+    display = "Request budget of {} requests is exceeded. Increase it with `--request-budget`",
+    budget
+  )]
+  RequestBudgetExceeded { budget: u32 },
+  #[fail(display = "Secret store error: {}", 0)]
+  SecretStore(String),
+  #[fail(display = "Cannot merge: {}. {}", reason, message)]
+  MergeRefused {
+    reason: &'static str,
+    message: String,
+  },
+  #[fail(display = "No recorded response for {} {} in cassette", method, url)]
+  ReplayMismatch { method: String, url: String },
+  #[fail(display = "OAuth error `{}`: {}", error, description)]
+  OAuth { error: String, description: String },
+  #[fail(
+    display = "ID of OAuth application is required but not specified. Register application \
+               in GitLab (User Settings > Applications) and pass its ID with `--client-id`"
+  )]
+  NoOAuthClientId,
+  #[fail(
+    display = "Template `{}` is found neither in local repository nor in `{}` of the project ({})",
+    name, git_ref, path
+  )]
+  TemplateNotFound {
+    name: String,
+    path: String,
+    git_ref: String,
+  },
+  #[fail(display = "Editor error: {}", 0)]
+  Editor(String),
+  #[fail(display = "Git error: {}", 0)]
+  Git(String),
 }
 
 impl From<reqwest::Error> for GLApiError {
@@ -24,18 +95,155 @@ impl From<reqwest::Error> for GLApiError {
   }
 }
 
-impl From<APIErr> for GLApiError {
-  fn from(ae: APIErr) -> Self {
-    let error = ae.error;
-    GLApiError::APIErr { error }
+impl GLApiError {
+  /// Classifies unsuccessful response of GitLab by its status
+  pub fn from_status(status: u16, retry_after: Option<u64>, body: String) -> Self {
+    let message = message_from_body(status, &body);
+    match status {
+      400 | 422 => match validation_errors(&body) {
+        Some(errors) => GLApiError::Validation {
+          status,
+          message: format_validation_errors(&errors),
+          errors,
+          body,
+        },
+        None => GLApiError::HttpStatus {
+          status,
+          message,
+          body,
+        },
+      },
+      401 => GLApiError::Unauthorized { message, body },
+      403 => GLApiError::Forbidden { message, body },
+      404 => GLApiError::NotFound { message, body },
+      409 => GLApiError::Conflict { message, body },
+      429 => GLApiError::RateLimited {
+        message,
+        retry_after,
+        body,
+      },
+      500..=599 => GLApiError::ServerError {
+        status,
+        message,
+        body,
+      },
+      _ => GLApiError::HttpStatus {
+        status,
+        message,
+        body,
+      },
+    }
+  }
+
+  /// HTTP status of response the error was created from
+  pub fn status(&self) -> Option<u16> {
+    match self {
+      GLApiError::Unauthorized { .. } => Some(401),
+      GLApiError::Forbidden { .. } => Some(403),
+      GLApiError::NotFound { .. } => Some(404),
+      GLApiError::Conflict { .. } => Some(409),
+      GLApiError::RateLimited { .. } => Some(429),
+      GLApiError::ServerError { status, .. }
+      | GLApiError::Validation { status, .. }
+      | GLApiError::HttpStatus { status, .. } => Some(*status),
+      _ => None,
+    }
+  }
+
+  /// Raw body of the response, to show it in verbose mode
+  pub fn body(&self) -> Option<&str> {
+    match self {
+      GLApiError::Unauthorized { body, .. }
+      | GLApiError::Forbidden { body, .. }
+      | GLApiError::NotFound { body, .. }
+      | GLApiError::Conflict { body, .. }
+      | GLApiError::RateLimited { body, .. }
+      | GLApiError::ServerError { body, .. }
+      | GLApiError::Validation { body, .. }
+      | GLApiError::HttpStatus { body, .. } => Some(body),
+      GLApiError::CantParseResp { resp_text } => Some(resp_text),
+      _ => None,
+    }
+  }
+
+  /// Human readable message of GitLab response
+  pub fn message(&self) -> Option<&str> {
+    match self {
+      GLApiError::Unauthorized { message, .. }
+      | GLApiError::Forbidden { message, .. }
+      | GLApiError::NotFound { message, .. }
+      | GLApiError::Conflict { message, .. }
+      | GLApiError::RateLimited { message, .. }
+      | GLApiError::ServerError { message, .. }
+      | GLApiError::Validation { message, .. }
+      | GLApiError::HttpStatus { message, .. } => Some(message),
+      _ => None,
+    }
+  }
+}
+
+/// Max length of message taken from a body that is not GitLab error
+const MAX_MESSAGE_LEN: usize = 100;
+
+/// Extracts human readable message from GitLab error response body.
+/// GitLab uses both `{"message": ...}` and `{"error": ...}` forms, and `message`
+/// may be an object with validation errors. Other bodies, e.g. HTML pages of proxies,
+/// are shortened to their first line or replaced with the reason of `status`
+pub fn message_from_body(status: u16, body: &str) -> String {
+  let val: Option<serde_json::Value> = serde_json::from_str(body).ok();
+  let msg = val
+    .as_ref()
+    .and_then(|v| v.get("message").or_else(|| v.get("error")));
+  match msg {
+    Some(serde_json::Value::String(s)) => s.to_owned(),
+    Some(m) => m.to_string(),
+    None => {
+      debug!("Response body: {}", body);
+      short_message(status, body)
+    }
+  }
+}
+
+fn short_message(status: u16, body: &str) -> String {
+  let line = body.lines().map(str::trim).find(|l| !l.is_empty());
+  match line {
+    Some(l) if !l.starts_with('<') => match l.char_indices().nth(MAX_MESSAGE_LEN) {
+      Some((i, _)) => format!("{}...", &l[..i]),
+      None => l.to_owned(),
+    },
+    _ => reqwest::StatusCode::from_u16(status)
+      .ok()
+      .and_then(|s| s.canonical_reason())
+      .unwrap_or("unexpected response")
+      .to_owned(),
   }
 }
 
-#[derive(Deserialize)]
-pub struct APIErr {
-  error: String,
+/// Parses `{"message": {"field": ["error", ...]}}` body of validation error
+fn validation_errors(body: &str) -> Option<BTreeMap<String, Vec<String>>> {
+  let val: serde_json::Value = serde_json::from_str(body).ok()?;
+  let fields = val.get("message")?.as_object()?;
+  let errors = fields
+    .iter()
+    .map(|(field, errs)| {
+      let errs = match errs {
+        serde_json::Value::Array(a) => a
+          .iter()
+          .map(|e| e.as_str().map_or_else(|| e.to_string(), str::to_owned))
+          .collect(),
+        serde_json::Value::String(s) => vec![s.to_owned()],
+        e => vec![e.to_string()],
+      };
+      (field.to_owned(), errs)
+    })
+    .collect();
+  Some(errors)
 }
 
-// impl fmt::Display for APIErr {
-//   fn display(f: fmt::Formatter, )
-// }
+fn format_validation_errors(errors: &BTreeMap<String, Vec<String>>) -> String {
+  errors
+    .iter()
+    .map(|(field, errs)| format!("{} {}", field, errs.join(", ")))
+    .collect::<Vec<String>>()
+    .join("; ")
+}
@@ -0,0 +1,54 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::api::{utils, GLApiError, GLApiResult};
+
+pub fn url_one(project: &str, file_path: &str) -> String {
+  format!(
+    "/projects/{}/repository/files/{}",
+    utils::encode(project),
+    utils::encode(file_path)
+  )
+}
+
+/// Query of file at the given branch, tag or commit
+#[derive(Serialize)]
+pub struct GetFileQuery<'a> {
+  #[serde(rename = "ref")]
+  pub git_ref: &'a str,
+}
+
+/// File in repository with its content
+#[derive(Serialize, Deserialize)]
+pub struct RepositoryFile {
+  pub file_name: String,
+  pub file_path: String,
+  pub size: u64,
+  /// Encoding of `content`, `base64` or `text`
+  pub encoding: String,
+  pub content: String,
+  #[serde(rename = "ref")]
+  pub git_ref: String,
+  // "file_name": "key.rb",
+  // "file_path": "app/models/key.rb",
+  // "size": 1476,
+  // "encoding": "base64",
+  // "content": "IyA9PSBTY2hlbWEgSW5mb3...",
+  // "content_sha256": "4c294617b60715c1d218e61164a3abd4808a4284cbc30e6728a01ad9aada4481",
+  // "ref": "main",
+  // "blob_id": "79f7bbd25901e8334750839545a9bd021f0e4c83",
+  // "commit_id": "d5a3ff139356ce33e37e73add446f16869741b50",
+  // "last_commit_id": "570e7b2abdd848b95f2f578043fc23bd6f6fd24d",
+}
+
+impl RepositoryFile {
+  /// Content as text
+  pub fn text(&self) -> GLApiResult<String> {
+    if self.encoding != "base64" {
+      return Ok(self.content.clone());
+    }
+    let bytes = STANDARD
+      .decode(self.content.trim())
+      .map_err(|e| GLApiError::ParseError(format!("content of `{}`: {}", self.file_path, e)))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+  }
+}
@@ -14,6 +14,9 @@ const STATE_CLOSED: &str = "closed";
 const STATE_LOCKED: &str = "locked";
 const STATE_MERGED: &str = "merged";
 
+const STATE_EVENT_CLOSE: &str = "close";
+const STATE_EVENT_REOPEN: &str = "reopen";
+
 // pub fn url_all() -> &'static str {
 //   "/merge_requests"
 // }
@@ -26,6 +29,18 @@ pub fn url_project_mr(project: &str) -> String {
   u
 }
 
+pub fn url_project_mr_one(project: &str, iid: u32) -> String {
+  format!("{}/{}", url_project_mr(project), iid)
+}
+
+pub fn url_project_mr_merge(project: &str, iid: u32) -> String {
+  format!("{}/merge", url_project_mr_one(project, iid))
+}
+
+pub fn url_project_mr_approvals(project: &str, iid: u32) -> String {
+  format!("{}/approvals", url_project_mr_one(project, iid))
+}
+
 // {
 //     "id": 1,
 //     "iid": 1,
@@ -118,7 +133,7 @@ pub fn url_project_mr(project: &str) -> String {
 //     }
 //   }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct MergeRequest {
   pub id: u32,
   pub iid: u32,
@@ -136,20 +151,88 @@ pub struct MergeRequest {
   pub source_branch: String,
   pub author: User,
   pub assignee: Option<User>,
+  #[serde(default)]
+  pub assignees: Vec<User>,
   pub source_project_id: u32,
   pub target_project_id: u32,
+  #[serde(default)]
+  pub labels: Vec<String>,
+  pub milestone: Option<Milestone>,
   pub work_in_progress: bool,
   pub merge_when_pipeline_succeeds: bool,
   pub merge_status: String,
   pub sha: Option<String>,
   pub merge_commit_sha: Option<String>,
-  // pub should_remove_source_branch: bool,
-  // pub force_remove_source_branch: bool,
+  pub user_notes_count: Option<u32>,
+  pub upvotes: Option<u32>,
+  pub downvotes: Option<u32>,
+  pub should_remove_source_branch: Option<bool>,
+  pub force_remove_source_branch: Option<bool>,
   // pub allow_collaboration: bool,
   // pub allow_maintainer_to_push: bool,
+  pub squash: Option<bool>,
+  /// Only present when a single merge request is requested
+  pub pipeline: Option<Pipeline>,
   pub web_url: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct Milestone {
+  pub id: u32,
+  pub iid: u32,
+  pub title: String,
+  pub state: String,
+  pub due_date: Option<String>,
+  pub web_url: Option<String>,
+}
+
+// "pipeline": {
+//   "id": 29626725,
+//   "sha": "2be7ddb704c7b6b83732fdd5b9f09d5a397b5f8f",
+//   "ref": "patch-28",
+//   "status": "success",
+//   "web_url": "https://gitlab.example.com/my-group/my-project/pipelines/29626725"
+// }
+#[derive(Serialize, Deserialize)]
+pub struct Pipeline {
+  pub id: u32,
+  pub sha: String,
+  #[serde(rename = "ref")]
+  pub git_ref: String,
+  pub status: String,
+  pub web_url: Option<String>,
+}
+
+// {
+//   "approvals_required": 2,
+//   "approvals_left": 1,
+//   "approved_by": [
+//     {
+//       "user": {
+//         "name": "Administrator",
+//         "username": "root",
+//         "id": 1,
+//         "state": "active",
+//         ...
+//       }
+//     }
+//   ]
+// }
+#[derive(Serialize, Deserialize)]
+pub struct MRApprovals {
+  /// Not available in GitLab Community Edition
+  pub approvals_required: Option<u32>,
+  /// Not available in GitLab Community Edition
+  pub approvals_left: Option<u32>,
+  #[serde(default)]
+  pub approved_by: Vec<MRApprover>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MRApprover {
+  pub user: User,
+}
+
 #[derive(Debug)]
 pub enum MRState {
   Opened,
@@ -187,6 +270,42 @@ impl Serialize for MRState {
   }
 }
 
+#[derive(Copy, Clone)]
+pub enum MRStateEvent {
+  Close,
+  Reopen,
+}
+
+impl FromStr for MRStateEvent {
+  type Err = GLApiError;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      STATE_EVENT_CLOSE => Ok(MRStateEvent::Close),
+      STATE_EVENT_REOPEN => Ok(MRStateEvent::Reopen),
+      _ => Err(GLApiError::ParseError(s.to_owned())),
+    }
+  }
+}
+
+impl fmt::Display for MRStateEvent {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let s = match self {
+      MRStateEvent::Close => STATE_EVENT_CLOSE,
+      MRStateEvent::Reopen => STATE_EVENT_REOPEN,
+    };
+    write!(f, "{}", s)
+  }
+}
+
+impl Serialize for MRStateEvent {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
 pub enum MRScope {
   CreatedByMe,
   AssignedToMe,
@@ -321,3 +440,97 @@ pub struct CreateMRBody<'a> {
   // allow_maintainer_to_push  boolean   no  Deprecated, see allow_collaboration
   // squash  boolean   no  Squash commits into a single commit when merging
 }
+
+/// Request body for updating an existing merge request.
+/// Only specified fields are sent, so the rest of them stay unchanged.
+#[derive(Default, Serialize)]
+pub struct UpdateMRBody<'a> {
+  /// Title of MR
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub title: Option<&'a str>,
+
+  /// Description of MR. Limited to 1 000 000 characters
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub description: Option<&'a str>,
+
+  /// The target branch
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub target_branch: Option<&'a str>,
+
+  /// The ID of the users to assign the merge request to. Empty list unassigns all users
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub assignee_ids: Option<Vec<u32>>,
+
+  /// Comma-separated label names. Empty string unassigns all labels
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub labels: Option<&'a str>,
+
+  /// The global ID of a milestone. Set to 0 to unassign a milestone
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub milestone_id: Option<u32>,
+
+  /// New state (close/reopen)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub state_event: Option<MRStateEvent>,
+
+  /// Flag indicating if a merge request should remove the source branch when merging
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub remove_source_branch: Option<bool>,
+
+  /// Squash commits into a single commit when merging
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub squash: Option<bool>,
+}
+
+impl<'a> UpdateMRBody<'a> {
+  pub fn is_empty(&self) -> bool {
+    self.title.is_none()
+      && self.description.is_none()
+      && self.target_branch.is_none()
+      && self.assignee_ids.is_none()
+      && self.labels.is_none()
+      && self.milestone_id.is_none()
+      && self.state_event.is_none()
+      && self.remove_source_branch.is_none()
+      && self.squash.is_none()
+  }
+}
+
+/// Request body for accepting a merge request.
+#[derive(Default, Serialize)]
+pub struct MergeMRBody<'a> {
+  /// Custom merge commit message
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub merge_commit_message: Option<&'a str>,
+
+  /// Custom squash commit message
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub squash_commit_message: Option<&'a str>,
+
+  /// If true the commits will be squashed into a single commit on merge
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub squash: Option<bool>,
+
+  /// If true removes the source branch
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub should_remove_source_branch: Option<bool>,
+
+  /// If true the MR is merged when the pipeline succeeds
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub merge_when_pipeline_succeeds: Option<bool>,
+
+  /// If present, then this SHA must match the HEAD of the source branch, otherwise the merge will fail
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sha: Option<&'a str>,
+}
+
+/// Explains why GitLab refused to accept a merge request, based on the response status.
+pub fn merge_refusal_reason(status: u16) -> Option<&'static str> {
+  match status {
+    401 => Some("you don't have permissions to accept this merge request"),
+    405 => Some("merge request is not able to be merged (it may be closed, a draft or blocked by a discussion)"),
+    406 => Some("merge request has conflicts, or the branch cannot be merged"),
+    409 => Some("`sha` does not match the HEAD of the source branch"),
+    _ => None,
+  }
+}
@@ -1,110 +1,415 @@
+#[cfg(feature = "async")]
+mod async_api;
 mod branches;
+mod cache;
+mod cassette;
+mod connection;
 mod error;
+mod files;
 mod merge_requests;
+mod oauth;
+mod pagination;
 mod projects;
+mod retry;
+mod tokens;
+mod transport;
 mod users;
 mod utils;
 
-pub use branches::{Branch, Commit, GetBranchesQuery};
-pub use merge_requests::{CreateMRBody, GetMergeRequestsQuery, MRScope, MRState, MergeRequest};
+#[cfg(feature = "async")]
+pub use async_api::{AsyncGLApi, GLFuture};
+pub use branches::{Branch, GetBranchesQuery};
+pub use cache::{ResponseCache, PROJECT_TTL, TOKEN_TTL, USERS_TTL};
+pub use cassette::{
+  Cassette, Interaction, RecordedRequest, RecordedResponse, RecordingTransport, ReplayTransport,
+};
+pub use connection::ConnectionSettings;
+pub use files::{GetFileQuery, RepositoryFile};
+pub use merge_requests::{
+  CreateMRBody, GetMergeRequestsQuery, MRApprovals, MRScope, MRState, MRStateEvent, MergeMRBody,
+  MergeRequest, UpdateMRBody,
+};
+pub use oauth::{
+  random_string, DeviceAuthorization, OAuthClient, OAuthSession, OAuthToken, Pkce, DEFAULT_SCOPES,
+};
+pub use pagination::Pagination;
 pub use projects::{GetProjectsQuery, Project, ProjectVisibility};
+pub use retry::RetryPolicy;
+pub use tokens::{PersonalAccessToken, EXPIRY_WARNING_DAYS, REQUIRED_SCOPE};
+pub use transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport};
 pub use users::{GetUsersQuery, User, UserState};
 
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, IF_NONE_MATCH};
+use reqwest::{Method, StatusCode, Url};
 use serde::{de::DeserializeOwned, Serialize};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 pub use error::GLApiError;
 pub type GLApiResult<T> = Result<T, failure::Error>;
 
-pub struct GLApi<'a> {
-  pub req_params: ReqParams<'a>,
+static DEFAULT_API_VERSION: &str = "v4";
+
+/// Blocking client of GitLab API
+pub struct GLApi {
+  pub req_params: ReqParams,
+  transport: Box<dyn Transport>,
+  /// Transport is `ReqwestTransport`, so pages can be requested with the async client
+  #[cfg(feature = "async")]
+  default_transport: bool,
+  /// Count of requests sent, to respect request budget.
+  /// Shared with the async client used for concurrent requests
+  requests_sent: Arc<AtomicU32>,
+  /// Set when `RateLimit-Remaining` of the last response is `0`
+  rate_limited_until: Cell<Option<Instant>>,
+  /// Cache of slow-changing resources, disabled by default
+  cache: Option<ResponseCache>,
+  /// Tokens of `login`, used instead of `req_params.private_token`
+  oauth: Option<OAuthSession>,
 }
 
-#[derive(Default)]
-pub struct ReqParams<'a> {
-  pub private_token: Option<&'a str>,
-  pub repo_url: Option<&'a str>,
-  pub default_project: Option<&'a str>,
+/// Parameters of requests to GitLab: where to send them and how to authenticate
+#[derive(Default, Clone)]
+pub struct ReqParams {
+  pub private_token: Option<String>,
+  /// URL of GitLab instance, e.g. `https://gitlab.com`
+  pub repo_url: Option<String>,
+  /// The ID or path of the project used when it is not passed to a method
+  pub default_project: Option<String>,
+  /// Version of GitLab API. Default is `v4`
+  pub api_version: Option<String>,
+  pub connection: ConnectionSettings,
+  pub retry: RetryPolicy,
+  /// Token is CI job token (`CI_JOB_TOKEN`) and is sent with `JOB-TOKEN` header
+  pub job_token: bool,
+  /// Token is OAuth access token and is sent with `Authorization: Bearer` header
+  pub oauth: bool,
+  /// Max count of requests sent at the same time by the async client. Default is 4
+  pub concurrency: Option<usize>,
 }
 
-impl<'a> ReqParams<'a> {
-  // pub fn new() -> Self {
-  //   ReqParams::default()
-  // }
+impl ReqParams {
+  pub fn new() -> Self {
+    ReqParams::default()
+  }
 
-  // pub fn set_private_token(&mut self, val: &'a str) {
-  //   self.private_token = Some(val);
-  // }
-  // pub fn set_repo_url(&mut self, val: &'a str) {
-  //   self.repo_url = Some(val);
-  // }
-  // pub fn set_default_project(&mut self, val: &'a str) {
-  //   self.default_project = Some(val);
-  // }
+  pub fn private_token<S: Into<String>>(mut self, val: S) -> Self {
+    self.private_token = Some(val.into());
+    self
+  }
+
+  pub fn repo_url<S: Into<String>>(mut self, val: S) -> Self {
+    self.repo_url = Some(val.into());
+    self
+  }
+
+  pub fn default_project<S: Into<String>>(mut self, val: S) -> Self {
+    self.default_project = Some(val.into());
+    self
+  }
+
+  pub fn api_version<S: Into<String>>(mut self, val: S) -> Self {
+    self.api_version = Some(val.into());
+    self
+  }
+
+  pub fn connection(mut self, val: ConnectionSettings) -> Self {
+    self.connection = val;
+    self
+  }
+
+  pub fn retry(mut self, val: RetryPolicy) -> Self {
+    self.retry = val;
+    self
+  }
+
+  /// Marks token as CI job token
+  pub fn job_token(mut self, val: bool) -> Self {
+    self.job_token = val;
+    self
+  }
+
+  /// Marks token as OAuth access token
+  pub fn oauth(mut self, val: bool) -> Self {
+    self.oauth = val;
+    self
+  }
+
+  pub fn concurrency(mut self, val: usize) -> Self {
+    self.concurrency = Some(val);
+    self
+  }
 
   pub fn get_private_token_checked(&self) -> GLApiResult<&str> {
     self
       .private_token
+      .as_deref()
       .ok_or_else(|| GLApiError::NoPrivateToken.into())
   }
+  /// Header authenticating request with `token`
+  pub(crate) fn auth_header(&self, token: &str) -> GLApiResult<(&'static str, HeaderValue)> {
+    let header = if self.oauth {
+      (
+        "Authorization",
+        HeaderValue::from_str(&format!("Bearer {}", token))?,
+      )
+    } else if self.job_token {
+      ("JOB-TOKEN", HeaderValue::from_str(token)?)
+    } else {
+      ("PRIVATE-TOKEN", HeaderValue::from_str(token)?)
+    };
+    Ok(header)
+  }
   pub fn get_repo_url_checked(&self) -> GLApiResult<&str> {
-    self.repo_url.ok_or_else(|| GLApiError::NoRepoUrl.into())
+    self
+      .repo_url
+      .as_deref()
+      .ok_or_else(|| GLApiError::NoRepoUrl.into())
   }
   pub fn get_default_project_checked(&self) -> GLApiResult<&str> {
     self
       .default_project
+      .as_deref()
       .ok_or_else(|| GLApiError::NoProject.into())
   }
+
+  /// Full URL of API endpoint
+  pub(crate) fn api_url(&self, url_part: &str) -> GLApiResult<String> {
+    let mut s = self.get_repo_url_checked()?.to_owned();
+    s.push_str("/api/");
+    s.push_str(self.api_version.as_deref().unwrap_or(DEFAULT_API_VERSION));
+    s.push_str(url_part);
+    Ok(s)
+  }
 }
 
-impl<'a> GLApi<'a> {
-  pub fn init(req_params: ReqParams<'a>) -> Self {
-    GLApi { req_params }
+impl GLApi {
+  pub fn init(req_params: ReqParams) -> Self {
+    let transport = ReqwestTransport::new(req_params.connection.clone());
+    #[cfg(feature = "async")]
+    {
+      GLApi {
+        default_transport: true,
+        ..GLApi::with_transport(req_params, transport)
+      }
+    }
+    #[cfg(not(feature = "async"))]
+    GLApi::with_transport(req_params, transport)
+  }
+
+  /// Creates client sending requests with the given transport, e.g. fake GitLab in tests.
+  /// Connection settings are not used then, and requests are sent one by one
+  /// even with `async` feature
+  pub fn with_transport<T: Transport + 'static>(req_params: ReqParams, transport: T) -> Self {
+    GLApi {
+      req_params,
+      transport: Box::new(transport),
+      #[cfg(feature = "async")]
+      default_transport: false,
+      requests_sent: Arc::new(AtomicU32::new(0)),
+      rate_limited_until: Cell::new(None),
+      cache: None,
+      oauth: None,
+    }
+  }
+
+  /// Authenticates requests with OAuth tokens, refreshing them when needed
+  pub fn oauth(mut self, session: OAuthSession) -> Self {
+    self.req_params.oauth = true;
+    self.oauth = Some(session);
+    self
+  }
+
+  /// Token to send with the next request
+  fn token(&self) -> GLApiResult<String> {
+    match &self.oauth {
+      Some(session) => session.access_token(),
+      None => Ok(self.req_params.get_private_token_checked()?.to_owned()),
+    }
+  }
+
+  /// Enables on-disk cache of projects and users
+  pub fn cache(mut self, cache: ResponseCache) -> Self {
+    self.cache = Some(cache);
+    self
+  }
+
+  /// Cache of responses of `url`, if they are cached
+  fn cache_for(&self, url: &Url) -> Option<&ResponseCache> {
+    self.cache.as_ref().filter(|c| c.ttl(url).is_some())
   }
 
   fn gen_url(&self, url_part: &str) -> GLApiResult<String> {
-    let mut s = self.req_params.get_repo_url_checked()?.to_owned();
-    s.push_str("/api/v4");
-    s.push_str(url_part);
-    Ok(s)
+    self.req_params.api_url(url_part)
   }
 
-  pub fn get<T, Q>(&self, url: &str, query: Option<&Q>) -> GLApiResult<T>
-  where
-    T: DeserializeOwned,
-    Q: Serialize + ?Sized,
-  {
-    let token = self.req_params.get_private_token_checked()?;
-    let full_url = self.gen_url(url)?;
+  fn spend_request_budget(&self) -> GLApiResult<()> {
+    let sent = self.requests_sent.fetch_add(1, Ordering::SeqCst);
+    match self.req_params.retry.budget() {
+      Some(budget) if sent >= budget => Err(GLApiError::RequestBudgetExceeded { budget }.into()),
+      _ => Ok(()),
+    }
+  }
+
+  /// Async client sharing request budget with this one
+  #[cfg(feature = "async")]
+  pub fn async_api(&self) -> GLApiResult<AsyncGLApi> {
+    let mut req_params = self.req_params.clone();
+    if self.oauth.is_some() {
+      req_params.private_token = Some(self.token()?);
+    }
+    AsyncGLApi::with_counter(req_params, self.requests_sent.clone())
+  }
+
+  /// Waits until rate limit is reset if GitLab reported that it is exhausted
+  fn wait_rate_limit(&self) {
+    if let Some(until) = self.rate_limited_until.take() {
+      let now = Instant::now();
+      if until > now {
+        let pause = until - now;
+        warn!(
+          "GitLab rate limit is exhausted, waiting {:.1}s",
+          pause.as_secs_f64()
+        );
+        thread::sleep(pause);
+      }
+    }
+  }
 
-    // println!("full_url {:?}", full_url);
-    // println!("query {:?}", query.serialize());
+  /// Sends request, retrying it on transient failures according to retry policy.
+  /// Responses of cached resources are taken from cache while they are fresh
+  /// and revalidated with `If-None-Match` after that.
+  /// OAuth access token rejected by GitLab is refreshed and request is sent again
+  fn execute(&self, method: Method, url: &Url, body: Option<Vec<u8>>) -> GLApiResult<HttpResponse> {
+    self.execute_with(method, url, body, &self.req_params.retry, true)
+  }
 
-    let mut req = reqwest::Client::new()
-      .get(&full_url)
-      .header("PRIVATE-TOKEN", token);
-    if let Some(q) = query {
-      req = req.query(q);
+  /// The same as `execute` with the given retry policy.
+  /// Requests that are not `budgeted` are not counted in request budget
+  fn execute_with(
+    &self,
+    method: Method,
+    url: &Url,
+    body: Option<Vec<u8>>,
+    policy: &RetryPolicy,
+    budgeted: bool,
+  ) -> GLApiResult<HttpResponse> {
+    let token = self.token()?;
+    let (auth_name, auth_value) = self.req_params.auth_header(&token)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(auth_name, auth_value);
+    if body.is_some() {
+      headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     }
-    let mut req = req.send()?;
 
-    let resp = req.text()?;
+    let cache = self.cache_for(url).filter(|_| method == Method::GET);
+    let cached = cache.and_then(|c| c.lookup(url, &token));
+    if let Some(c) = &cached {
+      if c.fresh {
+        info!("{} {} -> served from cache", method, url);
+        return Ok(c.response.clone());
+      }
+      if let Some(etag) = &c.etag {
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+      }
+    }
 
-    if let Ok(result) = serde_json::from_str(&resp) {
-      return Ok(result);
+    let req = HttpRequest {
+      method,
+      url: url.to_string(),
+      headers,
+      body,
+    };
+    let mut resp = self.send_with_retries(&req, policy, budgeted)?;
+    if let (StatusCode::UNAUTHORIZED, Some(session)) = (resp.status, &self.oauth) {
+      let mut req = req.clone();
+      let (auth_name, auth_value) = self.req_params.auth_header(&session.refresh()?)?;
+      req.headers.insert(auth_name, auth_value);
+      resp = self.send_with_retries(&req, policy, budgeted)?;
     }
 
-    println!("ERR>>>{:?}", resp);
-    if let Ok(api_err) = serde_json::from_str::<error::APIErr>(&resp) {
-      let e: GLApiError = api_err.into();
-      return Err(e.into());
+    if let Some(cache) = cache {
+      if let (StatusCode::NOT_MODIFIED, Some(c)) = (resp.status, cached) {
+        info!("{} {} -> not modified, served from cache", req.method, url);
+        resp = c.response;
+      }
+      // failed write of cache does not fail the command
+      if let Err(e) = cache.store(url, &token, &resp) {
+        warn!("Cannot write cache in {}: {}", cache.dir().display(), e);
+      }
     }
+    Ok(resp)
+  }
 
-    Err(
-      GLApiError::CantParseResp {
-        resp_text: req.text()?,
+  fn send_with_retries(
+    &self,
+    req: &HttpRequest,
+    policy: &RetryPolicy,
+    budgeted: bool,
+  ) -> GLApiResult<HttpResponse> {
+    let mut attempt = 0;
+
+    loop {
+      self.wait_rate_limit();
+      if budgeted {
+        self.spend_request_budget()?;
       }
-      .into(),
-    )
+
+      transport::log_request(req);
+      let started = Instant::now();
+      let (failure, delay) = match self.transport.send(req) {
+        Ok(resp) => {
+          transport::log_response(&req.method, &req.url, &resp, started.elapsed());
+          let pause = retry::rate_limit_pause(&resp.headers);
+          self
+            .rate_limited_until
+            .set(pause.map(|p| Instant::now() + p));
+          if !retry::is_retryable_status(resp.status)
+            || !policy.can_retry(&req.method, &req.url, attempt)
+          {
+            return Ok(resp);
+          }
+          match policy.delay(attempt, Some(&resp.headers)) {
+            Some(delay) => (resp.status.to_string(), delay),
+            None => return Ok(resp),
+          }
+        }
+        Err(e) => {
+          info!("{} {} failed: {}", req.method, req.url, e);
+          if !retry::is_transient_error(&e) || !policy.can_retry(&req.method, &req.url, attempt) {
+            return Err(e);
+          }
+          match policy.delay(attempt, None) {
+            Some(delay) => (e.to_string(), delay),
+            None => return Err(e),
+          }
+        }
+      };
+
+      // rate limit delay is already included
+      self.rate_limited_until.set(None);
+      warn!(
+        "{} {} failed ({}), retrying in {:.1}s",
+        req.method,
+        req.url,
+        failure,
+        delay.as_secs_f64()
+      );
+      thread::sleep(delay);
+      attempt += 1;
+    }
+  }
+
+  pub fn get<T, Q>(&self, url: &str, query: Option<&Q>) -> GLApiResult<T>
+  where
+    T: DeserializeOwned,
+    Q: Serialize + ?Sized,
+  {
+    let full_url = utils::url_with_query(&self.gen_url(url)?, query)?;
+    read_response(self.execute(Method::GET, &full_url, None)?)
   }
 
   pub fn post<T, B>(&self, url: &str, body: Option<&B>) -> GLApiResult<T>
@@ -112,39 +417,91 @@ impl<'a> GLApi<'a> {
     T: DeserializeOwned,
     B: Serialize + ?Sized,
   {
-    let token = self.req_params.get_private_token_checked()?;
-    let full_url = self.gen_url(url)?;
-    // println!("full_url {:?}", full_url);
+    let full_url = Url::parse(&self.gen_url(url)?)?;
+    let body = body.map(serde_json::to_vec).transpose()?;
+    read_response(self.execute(Method::POST, &full_url, body)?)
+  }
 
-    let mut req = reqwest::Client::new()
-      .post(&full_url)
-      .header("PRIVATE-TOKEN", token);
+  /// Requests list of items following pagination headers of GitLab response.
+  /// `Link` header is preferred as it works for both offset and keyset pagination,
+  /// `X-Next-Page` is used as a fallback.
+  /// With `async` feature all pages of offset pagination are requested concurrently
+  pub fn get_paged<T, Q>(
+    &self,
+    url: &str,
+    query: Option<&Q>,
+    pagination: &Pagination,
+  ) -> GLApiResult<Vec<T>>
+  where
+    T: DeserializeOwned + Send + 'static,
+    Q: Serialize + ?Sized,
+  {
+    let full_url = utils::url_with_query(&self.gen_url(url)?, query)?;
 
-    if let Some(b) = body {
-      req = req.json(b);
+    // pages of cached resources are requested one by one to be revalidated
+    #[cfg(feature = "async")]
+    {
+      if self.default_transport
+        && pagination.wants_more(0)
+        && !pagination.is_keyset()
+        && self.cache_for(&full_url).is_none()
+      {
+        self.wait_rate_limit();
+        let paged = || async_api::block_on(self.async_api()?.get_paged(url, query, pagination));
+        // async client has a snapshot of access token, so it is refreshed here like in `execute`
+        return match (paged(), &self.oauth) {
+          (Err(e), Some(session)) if is_unauthorized(&e) => {
+            session.refresh()?;
+            paged()
+          }
+          (res, _) => res,
+        };
+      }
     }
 
-    let mut req = req.send()?;
+    let mut page_url = pagination.page_url(&full_url, pagination.first_page());
+    let mut items: Vec<T> = Vec::new();
 
-    let resp = req.text()?;
+    loop {
+      let resp = self.execute(Method::GET, &page_url, None)?;
+      let headers = resp.headers.clone();
+      let mut page_items: Vec<T> = read_response(resp)?;
+      let is_empty_page = page_items.is_empty();
+      items.append(&mut page_items);
 
-    match serde_json::from_str(&resp) {
-      Ok(r) => return Ok(r),
-      Err(e) => println!("{:?}", e),
-    }
+      if is_empty_page || !pagination.wants_more(items.len()) {
+        break;
+      }
 
-    println!("ERR>>>{:?}", resp);
-    if let Ok(api_err) = serde_json::from_str::<error::APIErr>(&resp) {
-      let e: GLApiError = api_err.into();
-      return Err(e.into());
-    }
+      let header = |name: &str| {
+        headers
+          .get(name)
+          .and_then(|h| h.to_str().ok())
+          .map(str::trim)
+          .filter(|h| !h.is_empty())
+      };
 
-    Err(
-      GLApiError::CantParseResp {
-        resp_text: req.text()?,
+      if let Some(link) = header("link").and_then(utils::next_page_link) {
+        page_url = Url::parse(&link)?;
+      } else if let Some(next_page) = header("x-next-page").and_then(|p| p.parse().ok()) {
+        page_url = pagination.page_url(&full_url, next_page);
+      } else {
+        break;
       }
-      .into(),
-    )
+    }
+
+    pagination.truncate(&mut items);
+    Ok(items)
+  }
+
+  pub fn put<T, B>(&self, url: &str, body: Option<&B>) -> GLApiResult<T>
+  where
+    T: DeserializeOwned,
+    B: Serialize + ?Sized,
+  {
+    let full_url = Url::parse(&self.gen_url(url)?)?;
+    let body = body.map(serde_json::to_vec).transpose()?;
+    read_response(self.execute(Method::PUT, &full_url, body)?)
   }
 
   pub fn get_project(&self, project: &str) -> GLApiResult<projects::Project> {
@@ -152,14 +509,37 @@ impl<'a> GLApi<'a> {
     self.get(&projects::url_one(project), q)
   }
 
+  /// The user the token belongs to
+  pub fn get_current_user(&self) -> GLApiResult<users::User> {
+    let q: Option<&users::GetUsersQuery> = None;
+    self.get(users::url_current(), q)
+  }
+
+  /// Info of the private token requests are sent with, e.g. its scopes and expiry date.
+  /// Available since GitLab 16.0, and only for personal, project and group access tokens.
+  /// As a check before commands, it is sent once without retries and is not counted in request budget
+  pub fn get_current_token(&self) -> GLApiResult<tokens::PersonalAccessToken> {
+    let q: Option<&users::GetUsersQuery> = None;
+    let url = utils::url_with_query(&self.gen_url(tokens::url_self())?, q)?;
+    let policy = RetryPolicy::new().max_retries(0);
+    read_response(self.execute_with(Method::GET, &url, None, &policy, false)?)
+  }
+
   pub fn get_projects(
     &self,
     query: &projects::GetProjectsQuery,
+    pagination: &Pagination,
   ) -> GLApiResult<Vec<projects::Project>> {
-    self.get(projects::url_all(), Some(query))
+    // offset pagination is slow for the large lists of projects
+    let pagination = pagination.keyset_by_id();
+    self.get_paged(projects::url_all(), Some(query), &pagination)
   }
-  pub fn get_users(&self, query: &users::GetUsersQuery) -> GLApiResult<Vec<users::User>> {
-    self.get(users::url_all(), Some(query))
+  pub fn get_users(
+    &self,
+    query: &users::GetUsersQuery,
+    pagination: &Pagination,
+  ) -> GLApiResult<Vec<users::User>> {
+    self.get_paged(users::url_all(), Some(query), pagination)
   }
 
   // pub fn get_merge_requests(
@@ -172,13 +552,67 @@ impl<'a> GLApi<'a> {
     &self,
     project_in: Option<&str>,
     query: &merge_requests::GetMergeRequestsQuery,
+    pagination: &Pagination,
   ) -> GLApiResult<Vec<merge_requests::MergeRequest>> {
     let project = if let Some(pr) = project_in {
       pr
     } else {
       self.req_params.get_default_project_checked()?
     };
-    self.get(&merge_requests::url_project_mr(project), Some(query))
+    let url = merge_requests::url_project_mr(project);
+    self.get_paged(&url, Some(query), pagination)
+  }
+  /// Requests merge requests of several projects.
+  /// With `async` feature projects are requested concurrently
+  pub fn get_merge_requests_of_projects(
+    &self,
+    projects: &[&str],
+    query: &merge_requests::GetMergeRequestsQuery,
+    pagination: &Pagination,
+  ) -> GLApiResult<Vec<(String, Vec<merge_requests::MergeRequest>)>> {
+    #[cfg(feature = "async")]
+    {
+      if self.default_transport {
+        let fut = self
+          .async_api()?
+          .get_merge_requests_of_projects(projects, query, pagination);
+        return async_api::block_on(fut);
+      }
+    }
+
+    projects
+      .iter()
+      .map(|pr| {
+        let mrs = self.get_project_merge_requests(Some(pr), query, pagination)?;
+        Ok(((*pr).to_owned(), mrs))
+      })
+      .collect()
+  }
+  pub fn get_merge_request(
+    &self,
+    project_in: Option<&str>,
+    iid: u32,
+  ) -> GLApiResult<merge_requests::MergeRequest> {
+    let project = if let Some(pr) = project_in {
+      pr
+    } else {
+      self.req_params.get_default_project_checked()?
+    };
+    let q: Option<&merge_requests::GetMergeRequestsQuery> = None;
+    self.get(&merge_requests::url_project_mr_one(project, iid), q)
+  }
+  pub fn get_merge_request_approvals(
+    &self,
+    project_in: Option<&str>,
+    iid: u32,
+  ) -> GLApiResult<merge_requests::MRApprovals> {
+    let project = if let Some(pr) = project_in {
+      pr
+    } else {
+      self.req_params.get_default_project_checked()?
+    };
+    let q: Option<&merge_requests::GetMergeRequestsQuery> = None;
+    self.get(&merge_requests::url_project_mr_approvals(project, iid), q)
   }
   pub fn create_merge_request(
     &self,
@@ -187,16 +621,115 @@ impl<'a> GLApi<'a> {
   ) -> GLApiResult<merge_requests::MergeRequest> {
     self.post(&merge_requests::url_project_mr(project_id), Some(body))
   }
+  pub fn update_merge_request(
+    &self,
+    project_in: Option<&str>,
+    iid: u32,
+    body: &merge_requests::UpdateMRBody,
+  ) -> GLApiResult<merge_requests::MergeRequest> {
+    let project = if let Some(pr) = project_in {
+      pr
+    } else {
+      self.req_params.get_default_project_checked()?
+    };
+    self.put(
+      &merge_requests::url_project_mr_one(project, iid),
+      Some(body),
+    )
+  }
+  pub fn merge_merge_request(
+    &self,
+    project_in: Option<&str>,
+    iid: u32,
+    body: &merge_requests::MergeMRBody,
+  ) -> GLApiResult<merge_requests::MergeRequest> {
+    let project = if let Some(pr) = project_in {
+      pr
+    } else {
+      self.req_params.get_default_project_checked()?
+    };
+    let url = merge_requests::url_project_mr_merge(project, iid);
+    self.put(&url, Some(body)).map_err(|err| {
+      if let Some(e) = err.downcast_ref::<GLApiError>() {
+        if let Some(reason) = e.status().and_then(merge_requests::merge_refusal_reason) {
+          let message = e.message().unwrap_or_default().to_owned();
+          return GLApiError::MergeRefused { reason, message }.into();
+        }
+      }
+      err
+    })
+  }
+  /// File of repository at the given branch, tag or commit
+  pub fn get_file(
+    &self,
+    project_in: Option<&str>,
+    file_path: &str,
+    git_ref: &str,
+  ) -> GLApiResult<files::RepositoryFile> {
+    let project = if let Some(pr) = project_in {
+      pr
+    } else {
+      self.req_params.get_default_project_checked()?
+    };
+    let q = files::GetFileQuery { git_ref };
+    self.get(&files::url_one(project, file_path), Some(&q))
+  }
   pub fn get_project_branches(
     &self,
     project_in: Option<&str>,
     query: &branches::GetBranchesQuery,
+    pagination: &Pagination,
   ) -> GLApiResult<Vec<branches::Branch>> {
     let project = if let Some(pr) = project_in {
       pr
     } else {
       self.req_params.get_default_project_checked()?
     };
-    self.get(&branches::url_all(project), Some(query))
+    self.get_paged(&branches::url_all(project), Some(query), pagination)
+  }
+  pub fn get_project_branch(
+    &self,
+    project_in: Option<&str>,
+    branch: &str,
+  ) -> GLApiResult<branches::Branch> {
+    let project = if let Some(pr) = project_in {
+      pr
+    } else {
+      self.req_params.get_default_project_checked()?
+    };
+    let q: Option<&branches::GetBranchesQuery> = None;
+    self.get(&branches::url_one(project, branch), q)
+  }
+}
+
+#[cfg(feature = "async")]
+fn is_unauthorized(err: &failure::Error) -> bool {
+  matches!(
+    err.downcast_ref::<GLApiError>(),
+    Some(GLApiError::Unauthorized { .. })
+  )
+}
+
+/// Checks status of GitLab response and parses its body
+fn read_response<T: DeserializeOwned>(resp: HttpResponse) -> GLApiResult<T> {
+  parse_response(resp.status, &resp.headers, resp.body)
+}
+
+fn parse_response<T: DeserializeOwned>(
+  status: reqwest::StatusCode,
+  headers: &HeaderMap,
+  resp_text: String,
+) -> GLApiResult<T> {
+  if !status.is_success() {
+    let retry_after = headers
+      .get(reqwest::header::RETRY_AFTER)
+      .and_then(|h| h.to_str().ok())
+      .and_then(|h| h.trim().parse().ok());
+    return Err(GLApiError::from_status(status.as_u16(), retry_after, resp_text).into());
+  }
+
+  match serde_json::from_str(&resp_text) {
+    Ok(r) => Ok(r),
+    Err(_) => Err(GLApiError::CantParseResp { resp_text }.into()),
   }
 }
@@ -0,0 +1,344 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::{Method, Url};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::transport::{HttpRequest, Transport};
+use super::{GLApiError, GLApiResult};
+
+/// Scopes requested by `login`
+pub const DEFAULT_SCOPES: &str = "api";
+/// Access token is refreshed this many seconds before it expires
+const EXPIRY_MARGIN: u64 = 60;
+const DEVICE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Access and refresh tokens issued by GitLab
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OAuthToken {
+  pub access_token: String,
+  pub refresh_token: Option<String>,
+  /// Unix time when access token expires
+  pub expires_at: Option<u64>,
+  pub scope: Option<String>,
+}
+
+impl OAuthToken {
+  /// Access token is expired or expires in a minute
+  pub fn is_expired(&self) -> bool {
+    self
+      .expires_at
+      .is_some_and(|at| now_secs() + EXPIRY_MARGIN >= at)
+  }
+}
+
+/// Response of `/oauth/token`
+#[derive(Deserialize)]
+struct TokenResponse {
+  access_token: String,
+  refresh_token: Option<String>,
+  /// Seconds
+  expires_in: Option<u64>,
+  /// Unix time
+  created_at: Option<u64>,
+  scope: Option<String>,
+}
+
+impl From<TokenResponse> for OAuthToken {
+  fn from(resp: TokenResponse) -> Self {
+    let created_at = resp.created_at.unwrap_or_else(now_secs);
+    OAuthToken {
+      access_token: resp.access_token,
+      refresh_token: resp.refresh_token,
+      expires_at: resp.expires_in.map(|e| created_at + e),
+      scope: resp.scope,
+    }
+  }
+}
+
+/// Error response of OAuth endpoints
+#[derive(Deserialize)]
+struct ErrorResponse {
+  error: String,
+  error_description: Option<String>,
+}
+
+/// Response of `/oauth/authorize_device`: code the user enters on `verification_uri`
+#[derive(Deserialize, Clone)]
+pub struct DeviceAuthorization {
+  pub device_code: String,
+  pub user_code: String,
+  pub verification_uri: String,
+  /// `verification_uri` with `user_code` filled in
+  pub verification_uri_complete: Option<String>,
+  /// Seconds
+  pub expires_in: u64,
+  /// Seconds between polls of token
+  #[serde(default = "default_interval")]
+  pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+  5
+}
+
+/// Proof Key for Code Exchange with `S256` method
+pub struct Pkce {
+  pub verifier: String,
+  pub challenge: String,
+}
+
+impl Pkce {
+  pub fn new() -> Self {
+    let verifier = random_string();
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+    Pkce {
+      verifier,
+      challenge,
+    }
+  }
+}
+
+impl Default for Pkce {
+  fn default() -> Self {
+    Pkce::new()
+  }
+}
+
+/// Random URL safe string, e.g. for `state` parameter
+pub fn random_string() -> String {
+  let mut bytes = [0u8; 32];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_secs())
+}
+
+/// Client of OAuth endpoints of GitLab for application registered there.
+/// Application has to be public (not confidential), it is identified only by its ID
+pub struct OAuthClient {
+  repo_url: String,
+  client_id: String,
+  transport: Box<dyn Transport>,
+}
+
+impl OAuthClient {
+  pub fn new<T: Transport + 'static>(repo_url: &str, client_id: &str, transport: T) -> Self {
+    OAuthClient {
+      repo_url: repo_url.trim_end_matches('/').to_owned(),
+      client_id: client_id.to_owned(),
+      transport: Box::new(transport),
+    }
+  }
+
+  pub fn client_id(&self) -> &str {
+    &self.client_id
+  }
+
+  /// Page where the user authorizes application, which then redirects to `redirect_uri`
+  /// with `code` and `state`
+  pub fn authorize_url(
+    &self,
+    redirect_uri: &str,
+    scopes: &str,
+    state: &str,
+    pkce: &Pkce,
+  ) -> GLApiResult<Url> {
+    let url = Url::parse_with_params(
+      &format!("{}/oauth/authorize", self.repo_url),
+      &[
+        ("client_id", self.client_id.as_str()),
+        ("redirect_uri", redirect_uri),
+        ("response_type", "code"),
+        ("scope", scopes),
+        ("state", state),
+        ("code_challenge", pkce.challenge.as_str()),
+        ("code_challenge_method", "S256"),
+      ],
+    )?;
+    Ok(url)
+  }
+
+  /// Exchanges authorization code received on `redirect_uri` for tokens
+  pub fn exchange_code(
+    &self,
+    code: &str,
+    redirect_uri: &str,
+    pkce: &Pkce,
+  ) -> GLApiResult<OAuthToken> {
+    let resp: TokenResponse = self.post_form(
+      "/oauth/token",
+      &[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("code_verifier", pkce.verifier.as_str()),
+      ],
+    )?;
+    Ok(resp.into())
+  }
+
+  pub fn refresh(&self, refresh_token: &str) -> GLApiResult<OAuthToken> {
+    let resp: TokenResponse = self.post_form(
+      "/oauth/token",
+      &[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+      ],
+    )?;
+    Ok(resp.into())
+  }
+
+  /// Starts device authorization flow, supported by GitLab 17.2 and newer
+  pub fn authorize_device(&self, scopes: &str) -> GLApiResult<DeviceAuthorization> {
+    self.post_form("/oauth/authorize_device", &[("scope", scopes)])
+  }
+
+  /// Polls token until the user enters the code of device authorization
+  pub fn poll_device_token(&self, auth: &DeviceAuthorization) -> GLApiResult<OAuthToken> {
+    let deadline = Instant::now() + Duration::from_secs(auth.expires_in);
+    let mut interval = auth.interval;
+    loop {
+      thread::sleep(Duration::from_secs(interval));
+      let res: GLApiResult<TokenResponse> = self.post_form(
+        "/oauth/token",
+        &[
+          ("grant_type", DEVICE_GRANT_TYPE),
+          ("device_code", auth.device_code.as_str()),
+        ],
+      );
+      let err = match res {
+        Ok(resp) => return Ok(resp.into()),
+        Err(e) => e,
+      };
+      match err.downcast_ref::<GLApiError>() {
+        Some(GLApiError::OAuth { error, .. }) if error == "authorization_pending" => {}
+        Some(GLApiError::OAuth { error, .. }) if error == "slow_down" => interval += 5,
+        _ => return Err(err),
+      }
+      if Instant::now() >= deadline {
+        return Err(
+          GLApiError::OAuth {
+            error: "expired_token".to_owned(),
+            description: "device code is expired, run login again".to_owned(),
+          }
+          .into(),
+        );
+      }
+    }
+  }
+
+  /// Revokes token, so that neither it nor its refresh token can be used anymore
+  pub fn revoke(&self, token: &str) -> GLApiResult<()> {
+    let _: serde_json::Value = self.post_form("/oauth/revoke", &[("token", token)])?;
+    Ok(())
+  }
+
+  /// Sends form with client ID. Bodies are not logged, as they contain tokens
+  fn post_form<T: DeserializeOwned>(&self, path: &str, form: &[(&str, &str)]) -> GLApiResult<T> {
+    let mut form = form.to_vec();
+    form.push(("client_id", self.client_id.as_str()));
+    let body =
+      serde_urlencoded::to_string(&form).map_err(|e| GLApiError::ParseError(e.to_string()))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      CONTENT_TYPE,
+      HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    let req = HttpRequest {
+      method: Method::POST,
+      url: format!("{}{}", self.repo_url, path),
+      headers,
+      body: Some(body.into_bytes()),
+    };
+    let resp = self.transport.send(&req)?;
+    info!("{} {} -> {}", req.method, req.url, resp.status);
+
+    if resp.status.is_success() {
+      return serde_json::from_str(&resp.body)
+        .map_err(|_| GLApiError::ParseError(format!("unexpected response of {}", path)).into());
+    }
+    match serde_json::from_str::<ErrorResponse>(&resp.body) {
+      Ok(e) => {
+        let description = match e.error_description {
+          Some(d) => d,
+          None => e.error.clone(),
+        };
+        Err(
+          GLApiError::OAuth {
+            error: e.error,
+            description,
+          }
+          .into(),
+        )
+      }
+      Err(_) => Err(GLApiError::from_status(resp.status.as_u16(), None, resp.body).into()),
+    }
+  }
+}
+
+type RefreshCallback = Box<dyn Fn(&OAuthToken) -> GLApiResult<()>>;
+
+/// OAuth tokens used by `GLApi`. Access token is refreshed when it expires
+/// or GitLab rejects it, and new tokens are passed to callback to be saved
+pub struct OAuthSession {
+  client: OAuthClient,
+  token: RefCell<OAuthToken>,
+  on_refresh: Option<RefreshCallback>,
+}
+
+impl OAuthSession {
+  pub fn new(client: OAuthClient, token: OAuthToken) -> Self {
+    OAuthSession {
+      client,
+      token: RefCell::new(token),
+      on_refresh: None,
+    }
+  }
+
+  /// Sets callback called with new tokens after refresh.
+  /// GitLab revokes the old refresh token, so new tokens should be saved
+  pub fn on_refresh<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&OAuthToken) -> GLApiResult<()> + 'static,
+  {
+    self.on_refresh = Some(Box::new(callback));
+    self
+  }
+
+  /// Access token, refreshed if it is expired
+  pub fn access_token(&self) -> GLApiResult<String> {
+    if self.token.borrow().is_expired() {
+      return self.refresh();
+    }
+    Ok(self.token.borrow().access_token.clone())
+  }
+
+  /// Requests new access token with refresh token and returns it
+  pub fn refresh(&self) -> GLApiResult<String> {
+    let refresh_token = self
+      .token
+      .borrow()
+      .refresh_token
+      .clone()
+      .ok_or(GLApiError::NoPrivateToken)?;
+    info!("Refreshing OAuth access token");
+    let token = self.client.refresh(&refresh_token)?;
+    if let Some(callback) = &self.on_refresh {
+      callback(&token)?;
+    }
+    let access_token = token.access_token.clone();
+    self.token.replace(token);
+    Ok(access_token)
+  }
+}
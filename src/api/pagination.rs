@@ -0,0 +1,100 @@
+use reqwest::Url;
+
+/// Max value of `per_page` GitLab allows
+const MAX_PER_PAGE: usize = 100;
+
+/// Describes which part of paginated list should be fetched.
+/// By default all pages are requested one by one.
+#[derive(Default, Clone, Copy)]
+pub struct Pagination {
+  page: Option<u32>,
+  limit: Option<usize>,
+  keyset: bool,
+}
+
+impl Pagination {
+  pub fn new() -> Self {
+    Pagination::default()
+  }
+
+  /// Fetch only the given page
+  pub fn page(mut self, val: u32) -> Self {
+    self.page = Some(val);
+    self
+  }
+
+  /// Stop after given count of items fetched
+  pub fn limit(mut self, val: usize) -> Self {
+    self.limit = Some(val);
+    self
+  }
+
+  /// Use keyset pagination ordered by id if all pages are requested.
+  /// Only some of endpoints (e.g. projects) support it
+  pub(crate) fn keyset_by_id(mut self) -> Self {
+    self.keyset = self.page.is_none();
+    self
+  }
+
+  #[cfg(feature = "async")]
+  pub(crate) fn is_keyset(&self) -> bool {
+    self.keyset
+  }
+
+  pub(crate) fn first_page(&self) -> u32 {
+    self.page.unwrap_or(1)
+  }
+
+  pub(crate) fn per_page(&self) -> usize {
+    match self.limit {
+      Some(l) if l > 0 && l < MAX_PER_PAGE => l,
+      _ => MAX_PER_PAGE,
+    }
+  }
+
+  /// Whether next page should be requested when `fetched` items are received
+  pub(crate) fn wants_more(&self, fetched: usize) -> bool {
+    if self.page.is_some() {
+      return false;
+    }
+    match self.limit {
+      Some(l) => fetched < l,
+      None => true,
+    }
+  }
+
+  /// Last page that should be requested when the list has `total_pages`
+  #[cfg(feature = "async")]
+  pub(crate) fn last_page(&self, total_pages: u32) -> u32 {
+    match self.limit {
+      Some(l) if l > 0 => {
+        let pages = l.div_ceil(self.per_page()) as u32;
+        pages.min(total_pages)
+      }
+      _ => total_pages,
+    }
+  }
+
+  /// URL of the given page, for keyset pagination URL of the first page
+  pub(crate) fn page_url(&self, url: &Url, page: u32) -> Url {
+    let mut url = url.clone();
+    {
+      let mut pairs = url.query_pairs_mut();
+      if self.keyset {
+        pairs.append_pair("pagination", "keyset");
+        pairs.append_pair("order_by", "id");
+        pairs.append_pair("sort", "desc");
+      } else {
+        pairs.append_pair("page", &page.to_string());
+      }
+      pairs.append_pair("per_page", &self.per_page().to_string());
+    }
+    url
+  }
+
+  pub(crate) fn truncate<T>(&self, items: &mut Vec<T>) {
+    if let Some(l) = self.limit {
+      items.truncate(l);
+    }
+  }
+}
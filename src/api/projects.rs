@@ -19,7 +19,7 @@ pub fn url_one(project: &str) -> String {
   u
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Project {
   pub id: u32,
   pub description: Option<String>,
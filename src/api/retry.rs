@@ -0,0 +1,163 @@
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_BASE_DELAY_MS: u64 = 500;
+const DEFAULT_MAX_DELAY_MS: u64 = 30_000;
+const DEFAULT_MAX_SERVER_DELAY_SECS: u64 = 5 * 60;
+
+/// Describes how failed requests are retried.
+/// Requests are retried on connection errors, `429 Too Many Requests`
+/// and `502`, `503`, `504` statuses
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+  max_retries: u32,
+  base_delay: Duration,
+  max_delay: Duration,
+  max_server_delay: Duration,
+  retry_non_idempotent: bool,
+  request_budget: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy {
+      max_retries: DEFAULT_MAX_RETRIES,
+      base_delay: Duration::from_millis(DEFAULT_BASE_DELAY_MS),
+      max_delay: Duration::from_millis(DEFAULT_MAX_DELAY_MS),
+      max_server_delay: Duration::from_secs(DEFAULT_MAX_SERVER_DELAY_SECS),
+      retry_non_idempotent: false,
+      request_budget: None,
+    }
+  }
+}
+
+impl RetryPolicy {
+  pub fn new() -> Self {
+    RetryPolicy::default()
+  }
+
+  /// Count of retries after the first attempt. `0` disables retries
+  pub fn max_retries(mut self, val: u32) -> Self {
+    self.max_retries = val;
+    self
+  }
+
+  /// Longest delay requested by GitLab that is waited before retry. Default is 5 minutes
+  pub fn max_server_delay(mut self, val: Duration) -> Self {
+    self.max_server_delay = val;
+    self
+  }
+
+  /// Retry also `POST` and `PATCH` requests, which may create duplicates
+  pub fn retry_non_idempotent(mut self, val: bool) -> Self {
+    self.retry_non_idempotent = val;
+    self
+  }
+
+  /// Max count of requests, including retries, that a single command may send
+  pub fn request_budget(mut self, val: u32) -> Self {
+    self.request_budget = Some(val);
+    self
+  }
+
+  pub(crate) fn budget(&self) -> Option<u32> {
+    self.request_budget
+  }
+
+  pub(crate) fn can_retry(&self, method: &Method, url: &str, attempt: u32) -> bool {
+    attempt < self.max_retries && (is_idempotent(method, url) || self.retry_non_idempotent)
+  }
+
+  /// Exponential backoff with equal jitter, i.e. a random delay between the half and the whole
+  /// of the backoff, or delay requested by GitLab headers. The latter is not shortened,
+  /// `None` if it is longer than `max_server_delay`, so the request is not retried
+  pub(crate) fn delay(&self, attempt: u32, headers: Option<&HeaderMap>) -> Option<Duration> {
+    if let Some(d) = headers.and_then(requested_delay) {
+      if d > self.max_server_delay {
+        warn!(
+          "GitLab asks to wait {}s before retry, which is longer than {}s, giving up",
+          d.as_secs(),
+          self.max_server_delay.as_secs()
+        );
+        return None;
+      }
+      return Some(d);
+    }
+    let exp = self
+      .base_delay
+      .checked_mul(1 << attempt.min(16))
+      .unwrap_or(self.max_delay)
+      .min(self.max_delay);
+    let jitter = rand::thread_rng().gen_range(0.5..=1.0);
+    Some(exp.mul_f64(jitter))
+  }
+}
+
+/// Accepting merge request is `PUT`, but it is not idempotent: if the response of the first
+/// attempt is lost, the retry is refused because the merge request is already merged
+fn is_idempotent(method: &Method, url: &str) -> bool {
+  let path = url.split('?').next().unwrap_or(url);
+  match *method {
+    Method::PUT => !path.ends_with("/merge"),
+    Method::GET | Method::HEAD | Method::DELETE | Method::OPTIONS => true,
+    _ => false,
+  }
+}
+
+/// Whether request failed because of network, so that the next attempt may succeed:
+/// timeouts, connection and IO errors. Invalid requests, e.g. with bad URI or header, are not
+pub(crate) fn is_transient_error(err: &failure::Error) -> bool {
+  let re = match err.downcast_ref::<reqwest::Error>() {
+    Some(re) => re,
+    None => return false,
+  };
+  if re.is_timeout() {
+    return true;
+  }
+  re.get_ref().is_some_and(|inner| {
+    inner.is::<io::Error>()
+      || inner
+        .downcast_ref::<hyper::Error>()
+        .is_some_and(|h| !h.is_user() && !h.is_parse())
+  })
+}
+
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+  matches!(status.as_u16(), 429 | 502 | 503 | 504)
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+  headers
+    .get(name)
+    .and_then(|h| h.to_str().ok())
+    .and_then(|h| h.trim().parse().ok())
+}
+
+fn until_timestamp(ts: u64) -> Duration {
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+  Duration::from_secs(ts.saturating_sub(now))
+}
+
+/// Delay from `Retry-After` (in seconds) or `RateLimit-Reset` (unix time) headers
+fn requested_delay(headers: &HeaderMap) -> Option<Duration> {
+  if let Some(secs) = header_u64(headers, "retry-after") {
+    return Some(Duration::from_secs(secs));
+  }
+  header_u64(headers, "ratelimit-reset").map(until_timestamp)
+}
+
+/// Time to wait before the next request when rate limit of GitLab is exhausted,
+/// according to `RateLimit-Remaining` and `RateLimit-Reset` headers
+pub(crate) fn rate_limit_pause(headers: &HeaderMap) -> Option<Duration> {
+  if header_u64(headers, "ratelimit-remaining")? > 0 {
+    return None;
+  }
+  header_u64(headers, "ratelimit-reset").map(until_timestamp)
+}
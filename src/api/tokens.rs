@@ -0,0 +1,92 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn url_self() -> &'static str {
+  "/personal_access_tokens/self"
+}
+
+/// Scope needed to create and update merge requests
+pub const REQUIRED_SCOPE: &str = "api";
+/// Default count of days before expiry of token when commands start to warn about it
+pub const EXPIRY_WARNING_DAYS: u32 = 7;
+
+/// Personal (or project, group) access token the request is authenticated with
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PersonalAccessToken {
+  pub id: u32,
+  pub name: String,
+  pub revoked: bool,
+  pub active: bool,
+  pub scopes: Vec<String>,
+  pub user_id: u32,
+  /// Date in `YYYY-MM-DD` format, `None` for tokens without expiry
+  pub expires_at: Option<String>,
+  pub last_used_at: Option<String>,
+}
+
+impl PersonalAccessToken {
+  pub fn has_scope(&self, scope: &str) -> bool {
+    self.scopes.iter().any(|s| s == scope)
+  }
+
+  /// Days left until token expires, negative if it is already expired
+  pub fn days_until_expiry(&self) -> Option<i64> {
+    let expires_at = days_from_date(self.expires_at.as_deref()?)?;
+    let today = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |d| d.as_secs() / 86400) as i64;
+    Some(expires_at - today)
+  }
+
+  /// Problems of token worth warning about: it is inactive, expires within
+  /// `warning_days` or lacks scope needed to create merge requests
+  pub fn warnings(&self, warning_days: u32) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if self.revoked || !self.active {
+      warnings.push(format!("Private token `{}` is not active", self.name));
+    } else if let Some(days) = self.days_until_expiry() {
+      let when = match days {
+        d if d < 0 => None,
+        0 => Some("today".to_owned()),
+        1 => Some("tomorrow".to_owned()),
+        d => Some(format!("in {} days", d)),
+      };
+      match when {
+        None => warnings.push(format!("Private token `{}` is expired", self.name)),
+        Some(w) if days <= i64::from(warning_days) => warnings.push(format!(
+          "Private token `{}` expires {} ({}), rotate it in GitLab and run `config save-token`",
+          self.name,
+          w,
+          self.expires_at.as_deref().unwrap_or_default()
+        )),
+        Some(_) => {}
+      }
+    }
+    if !self.has_scope(REQUIRED_SCOPE) {
+      warnings.push(format!(
+        "Private token `{}` has no `{}` scope (scopes: {}), merge requests cannot be created or updated with it",
+        self.name,
+        REQUIRED_SCOPE,
+        self.scopes.join(", ")
+      ));
+    }
+    warnings
+  }
+}
+
+/// Days since Unix epoch of `YYYY-MM-DD` date
+fn days_from_date(date: &str) -> Option<i64> {
+  let mut parts = date.get(..10)?.splitn(3, '-');
+  let y: i64 = parts.next()?.parse().ok()?;
+  let m: i64 = parts.next()?.parse().ok()?;
+  let d: i64 = parts.next()?.parse().ok()?;
+  if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+    return None;
+  }
+  // days from civil algorithm of Howard Hinnant
+  let y = if m <= 2 { y - 1 } else { y };
+  let era = y.div_euclid(400);
+  let yoe = y - era * 400;
+  let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  Some(era * 146_097 + doe - 719_468)
+}
@@ -0,0 +1,144 @@
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::{ConnectionSettings, GLApiResult};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Headers whose values are never logged or written to cassette
+const SECRET_HEADERS: [&str; 5] = [
+  "private-token",
+  "job-token",
+  "authorization",
+  "cookie",
+  "set-cookie",
+];
+
+/// Request to GitLab API with serialized query and body
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+  pub method: Method,
+  /// Full URL including query
+  pub url: String,
+  /// Authentication and content type headers
+  pub headers: HeaderMap,
+  pub body: Option<Vec<u8>>,
+}
+
+/// Response of GitLab with its body read
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+  pub status: StatusCode,
+  pub headers: HeaderMap,
+  pub body: String,
+}
+
+/// Sends requests of `GLApi` and reads responses.
+/// The default one is `ReqwestTransport`, others can fake GitLab in tests.
+/// Error should wrap `reqwest::Error` for the request to be retried on connection failures
+pub trait Transport {
+  fn send(&self, req: &HttpRequest) -> GLApiResult<HttpResponse>;
+}
+
+/// Transport over blocking `reqwest` client, which is built on the first request
+pub struct ReqwestTransport {
+  connection: ConnectionSettings,
+  client: OnceCell<reqwest::Client>,
+}
+
+impl ReqwestTransport {
+  pub fn new(connection: ConnectionSettings) -> Self {
+    ReqwestTransport {
+      connection,
+      client: OnceCell::new(),
+    }
+  }
+
+  /// HTTP client shared by all requests
+  fn client(&self) -> GLApiResult<&reqwest::Client> {
+    if let Some(c) = self.client.get() {
+      return Ok(c);
+    }
+    let client = self.connection.build_client()?;
+    Ok(self.client.get_or_init(|| client))
+  }
+}
+
+impl Transport for ReqwestTransport {
+  fn send(&self, req: &HttpRequest) -> GLApiResult<HttpResponse> {
+    let mut builder = self
+      .client()?
+      .request(req.method.clone(), &req.url)
+      .headers(req.headers.clone());
+    if let Some(body) = &req.body {
+      builder = builder.body(body.clone());
+    }
+
+    let mut resp = builder.send()?;
+    let body = resp.text()?;
+    Ok(HttpResponse {
+      status: resp.status(),
+      headers: resp.headers().clone(),
+      body,
+    })
+  }
+}
+
+/// Headers as strings, with values of secret headers replaced.
+/// Values of repeated headers, e.g. `Link` or `Set-Cookie`, are kept in order
+pub(crate) fn redacted_headers(headers: &HeaderMap) -> BTreeMap<String, Vec<String>> {
+  let mut redacted: BTreeMap<String, Vec<String>> = BTreeMap::new();
+  for (name, value) in headers {
+    let value = if SECRET_HEADERS.contains(&name.as_str()) {
+      REDACTED.to_owned()
+    } else {
+      String::from_utf8_lossy(value.as_bytes()).into_owned()
+    };
+    redacted
+      .entry(name.as_str().to_owned())
+      .or_default()
+      .push(value);
+  }
+  redacted
+}
+
+/// Logs request before it is sent: headers with `debug` level, body with `trace` level
+pub(crate) fn log_request(req: &HttpRequest) {
+  debug!(
+    "{} {} headers: {:?}",
+    req.method,
+    req.url,
+    redacted_headers(&req.headers)
+  );
+  if let Some(body) = &req.body {
+    trace!(
+      "{} {} body: {}",
+      req.method,
+      req.url,
+      String::from_utf8_lossy(body)
+    );
+  }
+}
+
+/// Logs status, size and timing of response with `info` level,
+/// its headers with `debug` level and body with `trace` level
+pub(crate) fn log_response(method: &Method, url: &str, resp: &HttpResponse, elapsed: Duration) {
+  info!(
+    "{} {} -> {} ({} bytes, {} ms)",
+    method,
+    url,
+    resp.status,
+    resp.body.len(),
+    elapsed.as_millis()
+  );
+  debug!(
+    "{} {} response headers: {:?}",
+    method,
+    url,
+    redacted_headers(&resp.headers)
+  );
+  trace!("{} {} response body: {}", method, url, resp.body);
+}
@@ -8,10 +8,14 @@ pub fn url_all() -> &'static str {
   "/users"
 }
 
+pub fn url_current() -> &'static str {
+  "/user"
+}
+
 const STATE_ACTIVE: &str = "active";
 const STATE_BLOCKED: &str = "blocked";
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct User {
   // {
   //     "id": 1,
@@ -72,14 +76,11 @@ impl Serialize for UserState {
 pub struct GetUsersQuery<'a> {
   username: Option<&'a str>,
   state: Option<UserState>,
-  per_page: u8,
 }
 
 impl<'a> GetUsersQuery<'a> {
   pub fn new() -> Self {
-    let mut q = GetUsersQuery::default();
-    q.per_page = 100;
-    q
+    GetUsersQuery::default()
   }
 
   pub fn username(mut self, val: &'a str) -> Self {
@@ -98,7 +99,6 @@ impl<'a> Serialize for GetUsersQuery<'a> {
     S: Serializer,
   {
     let mut state = serializer.serialize_struct("GetUsersQuery", 2)?;
-    state.serialize_field("per_page", &self.per_page.to_string())?;
     if let Some(un) = self.username {
       state.serialize_field("username", un)?;
     }
@@ -1,5 +1,40 @@
 use percent_encoding::{percent_encode, PercentEncode, NON_ALPHANUMERIC};
+use reqwest::Url;
+use serde::Serialize;
 
-pub fn encode(s: &str) -> PercentEncode {
+use super::GLApiResult;
+
+pub fn encode(s: &str) -> PercentEncode<'_> {
   percent_encode(s.as_bytes(), NON_ALPHANUMERIC)
 }
+
+/// Finds URL of the next page in `Link` header.
+/// e.g. `<https://example.com/api/v4/projects?page=2>; rel="next", <...>; rel="last"`
+pub fn next_page_link(link_header: &str) -> Option<String> {
+  link_header.split(',').find_map(|part| {
+    let mut pieces = part.split(';');
+    let url = pieces.next()?.trim();
+    let is_next = pieces.any(|p| p.trim() == "rel=\"next\"");
+    if is_next && url.starts_with('<') && url.ends_with('>') {
+      Some(url[1..url.len() - 1].to_owned())
+    } else {
+      None
+    }
+  })
+}
+
+/// Parses URL and appends serialized query to it
+pub fn url_with_query<Q: Serialize + ?Sized>(url: &str, query: Option<&Q>) -> GLApiResult<Url> {
+  let mut url = Url::parse(url)?;
+  if let Some(q) = query {
+    let qs = serde_urlencoded::to_string(q)?;
+    if !qs.is_empty() {
+      let full = match url.query() {
+        Some(existing) if !existing.is_empty() => format!("{}&{}", existing, qs),
+        _ => qs,
+      };
+      url.set_query(Some(&full));
+    }
+  }
+  Ok(url)
+}
@@ -1,20 +1,67 @@
-use crate::api::{GetBranchesQuery, GetMergeRequestsQuery, GetProjectsQuery, GetUsersQuery};
+use crate::api::{
+  GetBranchesQuery, GetMergeRequestsQuery, GetProjectsQuery, GetUsersQuery, MergeMRBody, Pagination,
+};
+use crate::secrets::TokenStore;
 use clap::ArgMatches;
 
 pub enum Args<'a> {
-  LsUsers(GetUsersQuery<'a>),
-  LsProjects(GetProjectsQuery<'a>),
+  LsUsers {
+    query: GetUsersQuery<'a>,
+    pagination: Pagination,
+  },
+  LsProjects {
+    query: GetProjectsQuery<'a>,
+    pagination: Pagination,
+  },
   LsBranches {
     query: GetBranchesQuery<'a>,
     project: Option<&'a str>,
+    pagination: Pagination,
   },
   LsMr {
     project: Option<&'a str>,
+    /// Projects to request merge requests of concurrently, instead of a single project
+    projects: Option<Vec<&'a str>>,
     query: GetMergeRequestsQuery<'a>,
+    pagination: Pagination,
   },
   CreateMR(&'a ArgMatches<'a>),
-  CfgSaveToken(&'a str),
-  CfgShowToken,
-  CfgForgetToken,
+  ShowMr {
+    project: Option<&'a str>,
+    iid: u32,
+  },
+  UpdateMR {
+    project: Option<&'a str>,
+    iid: u32,
+    args_matches: &'a ArgMatches<'a>,
+  },
+  MergeMR {
+    project: Option<&'a str>,
+    iid: u32,
+    body: MergeMRBody<'a>,
+  },
+  CfgSaveToken {
+    token: &'a str,
+    host: Option<&'a str>,
+    store: TokenStore,
+  },
+  CfgShowToken {
+    host: Option<&'a str>,
+  },
+  CfgForgetToken {
+    host: Option<&'a str>,
+  },
+  CfgLsHosts,
+  CacheClear,
+  Login {
+    client_id: Option<&'a str>,
+    /// Device authorization instead of redirect to local port
+    device: bool,
+    port: Option<u16>,
+    scopes: Option<&'a str>,
+    store: TokenStore,
+  },
+  Logout,
+  Whoami,
   Unknown,
 }
@@ -1,4 +1,7 @@
 use super::ArgName;
+use crate::commit_summary;
+use crate::output;
+use crate::secrets;
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 const SUB_CMD_SETTINGS: [AppSettings; 4] = [
@@ -20,7 +23,7 @@ fn def_arg<'a, 'b, T: Into<&'static str>>(
   short: &'b str,
   help: &'a str,
 ) -> Arg<'a, 'b> {
-  arg_name(variant).help(help).short(short).takes_value(true)
+  def_flag(variant, short, help).takes_value(true)
 }
 
 fn def_flag<'a, 'b, T: Into<&'static str>>(
@@ -28,32 +31,161 @@ fn def_flag<'a, 'b, T: Into<&'static str>>(
   short: &'b str,
   help: &'a str,
 ) -> Arg<'a, 'b> {
-  arg_name(variant).help(help).short(short)
+  let name = variant.into();
+  Arg::with_name(name).long(name).help(help).short(short)
 }
 
 fn get_global_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
   vec![
     arg_name(ArgName::GlobalConfig)
+      .long(ArgName::GlobalConfig.into())
       .help("Path of global config file. e.g. for Linux it would be `~/.config/.mergereq-config`")
       .global(true)
       .takes_value(true),
     arg_name(ArgName::LocalConfig)
+      .long(ArgName::LocalConfig.into())
       .help("Path of local config file. Default is `.mergereqrc.toml` in the current directory.")
       .global(true)
       .takes_value(true),
     arg_name(ArgName::Project)
+      .long(ArgName::Project.into())
       .short("P")
       .help("The ID or path of the project owned by the authenticated user")
       .global(true)
       .takes_value(true),
+    arg_name(ArgName::PrivateToken)
+      .long(ArgName::PrivateToken.into())
+      .help("Sets the GitLab private token for requests")
+      .global(true)
+      .takes_value(true),
     arg_name(ArgName::RepoUrl)
+      .long(ArgName::RepoUrl.into())
       .help("URL of your Gitlab domain")
       .global(true)
       .takes_value(true),
+    arg_name(ArgName::Remote)
+      .long(ArgName::Remote.into())
+      .help("Name of git remote to infer URL of GitLab and project from, when they are not specified. Default is `origin`")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Output)
+      .long(ArgName::Output.into())
+      .short("o")
+      .help("Output format. Confirmation prompts are printed to stderr")
+      .possible_values(&output::POSSIBLE_VALUES)
+      .default_value("table")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Verbose)
+      .long(ArgName::Verbose.into())
+      .short("v")
+      .multiple(true)
+      .help(
+        "Logs requests with their status, timing and size and sources of settings. \
+         -vv adds headers, -vvv adds bodies. Also prints raw body of GitLab response on errors",
+      )
+      .global(true),
+    arg_name(ArgName::Debug)
+      .long(ArgName::Debug.into())
+      .help("The same as -vvv")
+      .global(true),
+    arg_name(ArgName::LogFile)
+      .long(ArgName::LogFile.into())
+      .help("Appends log to the given file instead of stderr. Warnings are still printed to stderr")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Retries)
+      .long(ArgName::Retries.into())
+      .help("Count of retries of requests failed with connection error, 429, 502, 503 or 504. Default is 3")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::RequestBudget)
+      .long(ArgName::RequestBudget.into())
+      .help("Max count of requests, including retries, the command may send")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::RetryNonIdempotent)
+      .long(ArgName::RetryNonIdempotent.into())
+      .help("Retry also POST requests (e.g. creating merge request). May create duplicates")
+      .global(true),
+    arg_name(ArgName::ConnectTimeout)
+      .long(ArgName::ConnectTimeout.into())
+      .help("Timeout of establishing connection, in seconds")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Timeout)
+      .long(ArgName::Timeout.into())
+      .help("Timeout of the whole request, in seconds. Default is 30")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Proxy)
+      .long(ArgName::Proxy.into())
+      .help("URL of HTTP(S) proxy. Default is taken from `https_proxy` or `http_proxy` environment variable")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::NoProxy)
+      .long(ArgName::NoProxy.into())
+      .help("Comma separated hosts that are requested without proxy, `*` for all hosts")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::CaCert)
+      .long(ArgName::CaCert.into())
+      .help("Path of PEM file (or bundle) with additional root certificates")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::ClientIdentity)
+      .long(ArgName::ClientIdentity.into())
+      .help("Path of PKCS#12 file with client certificate and key. Its password is read from `MERGEREQ_IDENTITY_PASSWORD`")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Insecure)
+      .long(ArgName::Insecure.into())
+      .help("Disables TLS certificate validation. DANGEROUS: the private token can be intercepted")
+      .global(true),
+    arg_name(ArgName::Concurrency)
+      .long(ArgName::Concurrency.into())
+      .help("Max count of requests sent at the same time, when built with `async` feature. Default is 4")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::Record)
+      .long(ArgName::Record.into())
+      .help("Writes every request to GitLab and its response to the given cassette file. Tokens are redacted")
+      .global(true)
+      .takes_value(true)
+      .conflicts_with(ArgName::Replay.into()),
+    arg_name(ArgName::Replay)
+      .long(ArgName::Replay.into())
+      .help("Serves responses from the given cassette file instead of sending requests to GitLab")
+      .global(true)
+      .takes_value(true),
+    arg_name(ArgName::NoCache)
+      .long(ArgName::NoCache.into())
+      .help("Requests projects and users from GitLab without using the response cache")
+      .global(true),
+  ]
+}
+
+fn get_pagination_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+  vec![
+    arg_name(ArgName::Limit)
+      .long(ArgName::Limit.into())
+      .help("Max count of items to show. All items are fetched by default")
+      .takes_value(true),
+    arg_name(ArgName::Page)
+      .long(ArgName::Page.into())
+      .help("Fetch only the given page of results (starts from 1)")
+      .takes_value(true),
   ]
 }
 
 fn get_config_subcmd<'a, 'b>() -> App<'a, 'b> {
+  let host_arg = || {
+    arg_name(ArgName::Host)
+      .long(ArgName::Host.into())
+      .help("GitLab host, e.g. `gitlab.example.com`. Default is the host of current repo_url, or `*` that matches any host")
+      .takes_value(true)
+  };
+
   sub_name(ArgName::Config)
     .about("Command to work with config")
     .settings(&SUB_CMD_SETTINGS)
@@ -61,32 +193,81 @@ fn get_config_subcmd<'a, 'b>() -> App<'a, 'b> {
       sub_name(ArgName::SaveToken)
         .about("Stores token to config file")
         .arg(
-          arg_name(ArgName::PrivateToken)
+          arg_name(ArgName::Token)
             .help("New GitLab private token to store")
             .required(true)
             .index(1),
+        )
+        .arg(host_arg())
+        .arg(
+          arg_name(ArgName::Store)
+            .long(ArgName::Store.into())
+            .help("Where to keep the token. `keyring` and `file` require `secret-store` feature. Default is `keyring` if it is enabled, `plain` otherwise")
+            .possible_values(&secrets::POSSIBLE_STORES)
+            .takes_value(true),
         ),
-      sub_name(ArgName::ShowToken).about("Shows GitLab private token if exists"),
-      sub_name(ArgName::ForgetToken).about("Removes global config file where private token is"),
+      sub_name(ArgName::ShowToken)
+        .about("Shows GitLab private token if exists")
+        .arg(host_arg()),
+      sub_name(ArgName::ForgetToken)
+        .about("Removes global config file where private token is. With `--host` removes only settings of that host")
+        .arg(host_arg()),
+      sub_name(ArgName::LsHosts).about("Shows list of GitLab hosts stored in global config"),
+    ])
+}
+
+fn get_cache_subcmd<'a, 'b>() -> App<'a, 'b> {
+  sub_name(ArgName::Cache)
+    .about("Command to work with cache of GitLab responses")
+    .settings(&SUB_CMD_SETTINGS)
+    .subcommands(vec![
+      sub_name(ArgName::Clear).about("Removes all cached responses")
     ])
 }
 
+fn get_login_subcmds<'a, 'b>() -> Vec<App<'a, 'b>> {
+  vec![
+    sub_name(ArgName::Login)
+      .about("Logs in to GitLab of repo_url with OAuth and stores access and refresh tokens in global config")
+      .args(&[
+        arg_name(ArgName::ClientId)
+          .long(ArgName::ClientId.into())
+          .help("ID of OAuth application registered in GitLab (not confidential, with `api` scope). It is saved for the host, so it is needed only once")
+          .takes_value(true),
+        arg_name(ArgName::Device)
+          .long(ArgName::Device.into())
+          .help("Uses device authorization (GitLab 17.2 and newer): enter the shown code on any device instead of redirecting browser to local port"),
+        arg_name(ArgName::Port)
+          .long(ArgName::Port.into())
+          .help("Local port GitLab redirects browser to. Redirect URI of the application should be `http://127.0.0.1:<port>/callback`. Default is 7171")
+          .takes_value(true),
+        arg_name(ArgName::Scopes)
+          .long(ArgName::Scopes.into())
+          .help("Space separated scopes to request. Default is `api`")
+          .takes_value(true),
+        arg_name(ArgName::Store)
+          .long(ArgName::Store.into())
+          .help("Where to keep the tokens. `keyring` and `file` require `secret-store` feature. Default is `keyring` if it is enabled, `plain` otherwise")
+          .possible_values(&secrets::POSSIBLE_STORES)
+          .takes_value(true),
+      ]),
+    sub_name(ArgName::Logout)
+      .about("Revokes tokens of `login` and removes them from global config"),
+    sub_name(ArgName::Whoami)
+      .about("Shows the user the token belongs to, and name, scopes and expiry date of the private token"),
+  ]
+}
+
 fn get_create_subcm<'a, 'b>() -> App<'a, 'b> {
   sub_name(ArgName::Create)
     .about("Created new merge requests")
     .settings(&SUB_CMD_SETTINGS)
-    .arg(
-      arg_name(ArgName::PrivateToken)
-        .help("Sets the GitLab private token for requests")
-        .takes_value(true)
-        .global(true),
-    )
     .subcommands(vec![sub_name(ArgName::Mr)
       .about("Creates merge request")
       .args(&[
         def_arg(ArgName::Title, "I", "Title of MR"),
-        def_arg(ArgName::SourceBranch, "S", "The source branch"),
-        def_arg(ArgName::TargetBranch, "T", "The target branch"),
+        def_arg(ArgName::SourceBranch, "S", "The source branch").long("src"),
+        def_arg(ArgName::TargetBranch, "T", "The target branch").long("trg"),
         def_arg(ArgName::AssigneeId, "G", "Assignee user ID"),
         def_arg(ArgName::AssigneeName, "A", "Assignee user name")
           .conflicts_with(ArgName::AssigneeId.into()),
@@ -94,7 +275,44 @@ fn get_create_subcm<'a, 'b>() -> App<'a, 'b> {
           ArgName::Description,
           "D",
           "Description of MR. Limited to 1 000 000 characters",
+        )
+        .long("desc"),
+        arg_name(ArgName::Template)
+          .long(ArgName::Template.into())
+          .help("Name of description template in `.gitlab/merge_request_templates`, read locally or from the target branch. Default is `mr_template` of local config")
+          .takes_value(true)
+          .conflicts_with(ArgName::Description.into()),
+        arg_name(ArgName::DescFromCommits)
+          .long(ArgName::DescFromCommits.into())
+          .help("Generates description from commits of the source branch: list of `subjects`, `bodies` of them, or `conventional` commits grouped by type. Default is `desc_from_commits` of local config")
+          .possible_values(&commit_summary::POSSIBLE_STYLES)
+          .takes_value(true)
+          .conflicts_with_all(&[ArgName::Description.into(), ArgName::Template.into()]),
+        def_flag(
+          ArgName::Edit,
+          "e",
+          "Composes title and description in `$VISUAL` or `$EDITOR`, pre-filled with them and with commits of the source branch",
         ),
+        arg_name(ArgName::Push)
+          .long(ArgName::Push.into())
+          .help("Pushes the source branch without asking if it is missing on the remote or behind local"),
+        arg_name(ArgName::NoPush)
+          .long(ArgName::NoPush.into())
+          .help("Does not check whether the source branch is pushed")
+          .conflicts_with_all(&[
+            ArgName::Push.into(),
+            ArgName::PushOption.into(),
+            ArgName::CreateOnPush.into(),
+          ]),
+        arg_name(ArgName::PushOption)
+          .long(ArgName::PushOption.into())
+          .help("Push option passed to `git push -o` when the source branch is pushed, e.g. `ci.skip`. May be repeated")
+          .takes_value(true)
+          .multiple(true)
+          .number_of_values(1),
+        arg_name(ArgName::CreateOnPush)
+          .long(ArgName::CreateOnPush.into())
+          .help("Creates merge request with `merge_request.*` push options while pushing the source branch, then sets the rest of its fields with API"),
         def_flag(
           ArgName::RemoveSourceBranch,
           "R",
@@ -130,7 +348,13 @@ fn get_lsmr_subsubcmd<'a, 'b>() -> App<'a, 'b> {
     def_arg(ArgName::AssigneeId, "A", "Returns merge requests assigned to the given user id. None returns unassigned merge requests. Any returns merge requests with an assignee."),
     def_arg(ArgName::SourceBranch, "R", "Return merge requests with the given source branch"),
     def_arg(ArgName::TargetBranch, "T", "Return merge requests with the given target branch"),
+    arg_name(ArgName::Projects)
+      .long(ArgName::Projects.into())
+      .help("Comma separated IDs or paths of projects to list merge requests of, instead of the default project")
+      .takes_value(true)
+      .use_delimiter(true),
   ])
+  .args(&get_pagination_args())
 }
 
 fn get_lsprojects_subsubcmd<'a, 'b>() -> App<'a, 'b> {
@@ -157,18 +381,125 @@ fn get_lsprojects_subsubcmd<'a, 'b>() -> App<'a, 'b> {
         "Limit by projects that the current user is a member of",
       ),
     ])
+    .args(&get_pagination_args())
+}
+
+fn get_show_subcmd<'a, 'b>() -> App<'a, 'b> {
+  sub_name(ArgName::Show)
+    .about("Prints detailed info about single entity")
+    .settings(&SUB_CMD_SETTINGS)
+    .subcommands(vec![sub_name(ArgName::Mr)
+      .about("Shows merge request in details")
+      .arg(
+        arg_name(ArgName::Iid)
+          .help("The internal ID of the merge request")
+          .required(true)
+          .index(1),
+      )])
+}
+
+fn get_update_subcmd<'a, 'b>() -> App<'a, 'b> {
+  let bool_possible_vals = ["true", "false"];
+
+  sub_name(ArgName::Update)
+    .about("Updates existing merge requests")
+    .settings(&SUB_CMD_SETTINGS)
+    .subcommands(vec![sub_name(ArgName::Mr)
+      .about("Updates merge request. Only specified attributes are changed")
+      .arg(
+        arg_name(ArgName::Iid)
+          .help("The internal ID of the merge request")
+          .required(true)
+          .index(1),
+      )
+      .args(&[
+        def_arg(ArgName::Title, "I", "Title of MR"),
+        def_arg(
+          ArgName::Description,
+          "D",
+          "Description of MR. Limited to 1 000 000 characters",
+        )
+        .long("desc"),
+        def_arg(ArgName::TargetBranch, "T", "The target branch").long("trg"),
+        def_arg(
+          ArgName::AssigneeId,
+          "G",
+          "Comma separated IDs of the users to assign MR to. Set to 0 to unassign all users",
+        )
+        .use_delimiter(true),
+        def_arg(ArgName::AssigneeName, "A", "Assignee user name")
+          .conflicts_with(ArgName::AssigneeId.into()),
+        def_arg(
+          ArgName::Labels,
+          "L",
+          "Comma separated label names. Empty string unassigns all labels",
+        )
+        .empty_values(true),
+        def_arg(
+          ArgName::MilestoneId,
+          "M",
+          "The global ID of a milestone. Set to 0 to unassign a milestone",
+        ),
+        def_arg(ArgName::StateEvent, "E", "New state of MR").possible_values(&["close", "reopen"]),
+        def_arg(
+          ArgName::RemoveSourceBranch,
+          "R",
+          "Flag indicating if a merge request should remove the source branch when merging",
+        )
+        .possible_values(&bool_possible_vals),
+        def_arg(
+          ArgName::Squash,
+          "Q",
+          "Squash commits into a single commit when merging",
+        )
+        .possible_values(&bool_possible_vals),
+      ])])
+}
+
+fn get_merge_subcmd<'a, 'b>() -> App<'a, 'b> {
+  sub_name(ArgName::Merge)
+    .about("Accepts merge requests")
+    .settings(&SUB_CMD_SETTINGS)
+    .subcommands(vec![sub_name(ArgName::Mr)
+      .about("Merges merge request")
+      .arg(
+        arg_name(ArgName::Iid)
+          .help("The internal ID of the merge request")
+          .required(true)
+          .index(1),
+      )
+      .args(&[
+        def_arg(ArgName::MergeCommitMessage, "M", "Custom merge commit message"),
+        arg_name(ArgName::SquashCommitMessage)
+          .long(ArgName::SquashCommitMessage.into())
+          .help("Custom squash commit message")
+          .takes_value(true),
+        def_flag(
+          ArgName::Squash,
+          "Q",
+          "Squash commits into a single commit when merging",
+        ),
+        def_flag(
+          ArgName::RemoveSourceBranch,
+          "R",
+          "Remove the source branch after merge",
+        ),
+        arg_name(ArgName::Sha)
+          .long(ArgName::Sha.into())
+          .help("If present, then this SHA must match the HEAD of the source branch, otherwise the merge will fail")
+          .takes_value(true),
+        def_flag(
+          ArgName::WhenPipelineSucceeds,
+          "W",
+          "Merge when the pipeline succeeds instead of immediately",
+        ),
+      ])])
 }
 
 fn get_ls_subcmd<'a, 'b>() -> App<'a, 'b> {
   sub_name(ArgName::Ls)
     .about("Prints info about everything")
     .settings(&SUB_CMD_SETTINGS)
-    .arg(
-      arg_name(ArgName::PrivateToken)
-        .help("Sets the GitLab private token for requests")
-        .takes_value(true)
-        .global(true),
-    )
     .subcommands(vec![
       get_lsmr_subsubcmd(),
       get_lsprojects_subsubcmd(),
@@ -179,12 +510,14 @@ fn get_ls_subcmd<'a, 'b>() -> App<'a, 'b> {
           def_flag(ArgName::Active, "A", "Show only active users"),
           def_flag(ArgName::Blocked, "B", "Show only blocked users")
             .conflicts_with(ArgName::Active.into()),
-        ]),
+        ])
+        .args(&get_pagination_args()),
       sub_name(ArgName::Branches)
         .about("Shows list of branches")
         .args(&[
           def_arg(ArgName::Search, "S", "Return list of branches containing the search string. You can use ^term and term$ to find branches that begin and end with term respectively.")
-        ]),
+        ])
+        .args(&get_pagination_args()),
 
     ])
 }
@@ -196,9 +529,14 @@ pub fn get_matches<'a>() -> ArgMatches<'a> {
     .settings(&SUB_CMD_SETTINGS)
     .args(&get_global_args())
     .subcommands(vec![
+      get_cache_subcmd(),
       get_config_subcmd(),
       get_create_subcm(),
       get_ls_subcmd(),
+      get_show_subcmd(),
+      get_update_subcmd(),
+      get_merge_subcmd(),
     ])
+    .subcommands(get_login_subcmds())
     .get_matches()
 }
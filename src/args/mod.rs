@@ -3,8 +3,9 @@ use std::str::FromStr;
 
 use crate::api::{
   GetBranchesQuery, GetMergeRequestsQuery, GetProjectsQuery, GetUsersQuery, MRScope, MRState,
-  ProjectVisibility, UserState,
+  MergeMRBody, Pagination, ProjectVisibility, UserState,
 };
+use crate::secrets::TokenStore;
 
 mod defs;
 mod matches;
@@ -21,6 +22,22 @@ pub fn parse_args<'a>(arg_matches: &'a ArgMatches) -> Args<'a> {
     return handle_create(m);
   } else if let Some(m) = arg_matches.subcommand_matches(ArgName::Config) {
     return handle_config(m);
+  } else if let Some(m) = arg_matches.subcommand_matches(ArgName::Show) {
+    return handle_show(m);
+  } else if let Some(m) = arg_matches.subcommand_matches(ArgName::Update) {
+    return handle_update(m);
+  } else if let Some(m) = arg_matches.subcommand_matches(ArgName::Merge) {
+    return handle_merge(m);
+  } else if let Some(m) = arg_matches.subcommand_matches(ArgName::Login) {
+    return handle_login(m);
+  } else if arg_matches.subcommand_matches(ArgName::Logout).is_some() {
+    return Args::Logout;
+  } else if arg_matches.subcommand_matches(ArgName::Whoami).is_some() {
+    return Args::Whoami;
+  } else if let Some(m) = arg_matches.subcommand_matches(ArgName::Cache) {
+    if m.subcommand_matches(ArgName::Clear).is_some() {
+      return Args::CacheClear;
+    }
   }
 
   Args::Unknown
@@ -47,18 +64,117 @@ fn handle_create<'a>(mat: &'a ArgMatches) -> Args<'a> {
   Args::Unknown
 }
 
+fn parse_iid(m: &ArgMatches) -> u32 {
+  let iid_arg: &str = ArgName::Iid.into();
+  value_t!(m, iid_arg, u32).unwrap_or_else(|e| e.exit())
+}
+
+fn handle_show<'a>(mat: &'a ArgMatches) -> Args<'a> {
+  if let Some(m) = mat.subcommand_matches(ArgName::Mr) {
+    return Args::ShowMr {
+      project: m.value_of(ArgName::Project),
+      iid: parse_iid(m),
+    };
+  }
+  Args::Unknown
+}
+
+fn handle_update<'a>(mat: &'a ArgMatches) -> Args<'a> {
+  if let Some(m) = mat.subcommand_matches(ArgName::Mr) {
+    return Args::UpdateMR {
+      project: m.value_of(ArgName::Project),
+      iid: parse_iid(m),
+      args_matches: m,
+    };
+  }
+  Args::Unknown
+}
+
+fn handle_merge<'a>(mat: &'a ArgMatches) -> Args<'a> {
+  if let Some(m) = mat.subcommand_matches(ArgName::Mr) {
+    let mut body = MergeMRBody {
+      merge_commit_message: m.value_of(ArgName::MergeCommitMessage),
+      squash_commit_message: m.value_of(ArgName::SquashCommitMessage),
+      sha: m.value_of(ArgName::Sha),
+      ..Default::default()
+    };
+    if m.is_present(ArgName::Squash) {
+      body.squash = Some(true);
+    }
+    if m.is_present(ArgName::RemoveSourceBranch) {
+      body.should_remove_source_branch = Some(true);
+    }
+    if m.is_present(ArgName::WhenPipelineSucceeds) {
+      body.merge_when_pipeline_succeeds = Some(true);
+    }
+    return Args::MergeMR {
+      project: m.value_of(ArgName::Project),
+      iid: parse_iid(m),
+      body,
+    };
+  }
+  Args::Unknown
+}
+
+fn handle_login<'a>(m: &'a ArgMatches) -> Args<'a> {
+  let port = if m.is_present(ArgName::Port) {
+    let port_arg: &str = ArgName::Port.into();
+    Some(value_t!(m, port_arg, u16).unwrap_or_else(|e| e.exit()))
+  } else {
+    None
+  };
+  Args::Login {
+    client_id: m.value_of(ArgName::ClientId),
+    device: m.is_present(ArgName::Device),
+    port,
+    scopes: m.value_of(ArgName::Scopes),
+    store: m
+      .value_of(ArgName::Store)
+      .and_then(|s| TokenStore::from_str(s).ok())
+      .unwrap_or_else(TokenStore::default_for_build),
+  }
+}
+
 fn handle_config<'a>(mat: &'a ArgMatches) -> Args<'a> {
   if let Some(m) = mat.subcommand_matches(ArgName::SaveToken) {
-    let token = m.value_of(ArgName::PrivateToken).unwrap();
-    return Args::CfgSaveToken(token);
-  } else if mat.is_present(ArgName::ShowToken) {
-    return Args::CfgShowToken;
-  } else if mat.is_present(ArgName::ForgetToken) {
-    return Args::CfgForgetToken;
+    let token = m.value_of(ArgName::Token).unwrap();
+    let store = m
+      .value_of(ArgName::Store)
+      .and_then(|s| TokenStore::from_str(s).ok())
+      .unwrap_or_else(TokenStore::default_for_build);
+    return Args::CfgSaveToken {
+      token,
+      host: m.value_of(ArgName::Host),
+      store,
+    };
+  } else if let Some(m) = mat.subcommand_matches(ArgName::ShowToken) {
+    return Args::CfgShowToken {
+      host: m.value_of(ArgName::Host),
+    };
+  } else if let Some(m) = mat.subcommand_matches(ArgName::ForgetToken) {
+    return Args::CfgForgetToken {
+      host: m.value_of(ArgName::Host),
+    };
+  } else if mat.is_present(ArgName::LsHosts) {
+    return Args::CfgLsHosts;
   }
   Args::Unknown
 }
 
+fn parse_pagination(m: &ArgMatches) -> Pagination {
+  let limit_arg: &str = ArgName::Limit.into();
+  let page_arg: &str = ArgName::Page.into();
+
+  let mut pagination = Pagination::new();
+  if m.is_present(limit_arg) {
+    pagination = pagination.limit(value_t!(m, limit_arg, usize).unwrap_or_else(|e| e.exit()));
+  }
+  if m.is_present(page_arg) {
+    pagination = pagination.page(value_t!(m, page_arg, u32).unwrap_or_else(|e| e.exit()));
+  }
+  pagination
+}
+
 fn handle_ls_projects<'a>(m: &'a ArgMatches) -> Args<'a> {
   let mut q = GetProjectsQuery::new();
   if let Some(s) = m.value_of(ArgName::Search) {
@@ -80,7 +196,10 @@ fn handle_ls_projects<'a>(m: &'a ArgMatches) -> Args<'a> {
   if m.is_present(ArgName::Membership) {
     q = q.membership(true);
   }
-  Args::LsProjects(q)
+  Args::LsProjects {
+    query: q,
+    pagination: parse_pagination(m),
+  }
 }
 
 fn handle_ls_users<'a>(m: &'a ArgMatches) -> Args<'a> {
@@ -95,7 +214,10 @@ fn handle_ls_users<'a>(m: &'a ArgMatches) -> Args<'a> {
     q = q.state(UserState::Blocked);
   }
 
-  Args::LsUsers(q)
+  Args::LsUsers {
+    query: q,
+    pagination: parse_pagination(m),
+  }
 }
 
 fn handle_ls_branches<'a>(m: &'a ArgMatches) -> Args<'a> {
@@ -106,6 +228,7 @@ fn handle_ls_branches<'a>(m: &'a ArgMatches) -> Args<'a> {
   Args::LsBranches {
     query: q,
     project: m.value_of(ArgName::Project),
+    pagination: parse_pagination(m),
   }
 }
 
@@ -143,6 +266,8 @@ fn handle_ls_mr<'a>(m: &'a ArgMatches) -> Args<'a> {
 
   Args::LsMr {
     project: m.value_of(ArgName::Project),
+    projects: m.values_of(ArgName::Projects).map(|v| v.collect()),
     query: q,
+    pagination: parse_pagination(m),
   }
 }
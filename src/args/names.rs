@@ -3,6 +3,7 @@ use std::convert::AsRef;
 #[derive(Debug, Clone)]
 pub enum ArgName {
   PrivateToken,
+  Token,
   GlobalConfig,
   LocalConfig,
   Project,
@@ -36,12 +37,64 @@ pub enum ArgName {
   Archived,
   Owned,
   Membership,
+  Show,
+  Iid,
+  Merge,
+  MergeCommitMessage,
+  SquashCommitMessage,
+  Sha,
+  WhenPipelineSucceeds,
+  Update,
+  Labels,
+  MilestoneId,
+  StateEvent,
+  Limit,
+  Page,
+  Output,
+  Remote,
+  Host,
+  LsHosts,
+  Store,
+  Verbose,
+  Retries,
+  RequestBudget,
+  RetryNonIdempotent,
+  ConnectTimeout,
+  Timeout,
+  Proxy,
+  NoProxy,
+  CaCert,
+  ClientIdentity,
+  Insecure,
+  Concurrency,
+  Record,
+  Replay,
+  Debug,
+  LogFile,
+  NoCache,
+  Cache,
+  Clear,
+  Login,
+  Logout,
+  ClientId,
+  Device,
+  Port,
+  Scopes,
+  Whoami,
+  Template,
+  Edit,
+  DescFromCommits,
+  Push,
+  NoPush,
+  PushOption,
+  CreateOnPush,
 }
 
 impl From<ArgName> for &str {
   fn from(arg_name: ArgName) -> &'static str {
     match arg_name {
       ArgName::PrivateToken => "private-token",
+      ArgName::Token => "token",
       ArgName::GlobalConfig => "global-config",
       ArgName::LocalConfig => "local-config",
       ArgName::Project => "project",
@@ -69,12 +122,63 @@ impl From<ArgName> for &str {
       ArgName::Search => "search",
       ArgName::State => "state",
       ArgName::Scope => "scope",
-      ArgName::AuthorId => "authorId",
+      ArgName::AuthorId => "author-id",
       ArgName::Projects => "projects",
       ArgName::Visibility => "visibility",
       ArgName::Archived => "archived",
       ArgName::Owned => "owned",
       ArgName::Membership => "membership",
+      ArgName::Show => "show",
+      ArgName::Iid => "iid",
+      ArgName::Merge => "merge",
+      ArgName::MergeCommitMessage => "message",
+      ArgName::SquashCommitMessage => "squash-message",
+      ArgName::Sha => "sha",
+      ArgName::WhenPipelineSucceeds => "when-pipeline-succeeds",
+      ArgName::Update => "update",
+      ArgName::Labels => "labels",
+      ArgName::MilestoneId => "milestone-id",
+      ArgName::StateEvent => "state-event",
+      ArgName::Limit => "limit",
+      ArgName::Page => "page",
+      ArgName::Output => "output",
+      ArgName::Remote => "remote",
+      ArgName::Host => "host",
+      ArgName::LsHosts => "ls-hosts",
+      ArgName::Store => "store",
+      ArgName::Verbose => "verbose",
+      ArgName::Retries => "retries",
+      ArgName::RequestBudget => "request-budget",
+      ArgName::RetryNonIdempotent => "retry-non-idempotent",
+      ArgName::ConnectTimeout => "connect-timeout",
+      ArgName::Timeout => "timeout",
+      ArgName::Proxy => "proxy",
+      ArgName::NoProxy => "no-proxy",
+      ArgName::CaCert => "ca-cert",
+      ArgName::ClientIdentity => "client-identity",
+      ArgName::Insecure => "insecure",
+      ArgName::Concurrency => "concurrency",
+      ArgName::Record => "record",
+      ArgName::Replay => "replay",
+      ArgName::Debug => "debug",
+      ArgName::LogFile => "log-file",
+      ArgName::NoCache => "no-cache",
+      ArgName::Cache => "cache",
+      ArgName::Clear => "clear",
+      ArgName::Login => "login",
+      ArgName::Logout => "logout",
+      ArgName::ClientId => "client-id",
+      ArgName::Device => "device",
+      ArgName::Port => "port",
+      ArgName::Scopes => "scopes",
+      ArgName::Whoami => "whoami",
+      ArgName::Template => "template",
+      ArgName::Edit => "edit",
+      ArgName::DescFromCommits => "desc-from-commits",
+      ArgName::Push => "push",
+      ArgName::NoPush => "no-push",
+      ArgName::PushOption => "push-option",
+      ArgName::CreateOnPush => "create-on-push",
     }
   }
 }
@@ -0,0 +1,160 @@
+use mergereq::api::GLApiError;
+use std::str::FromStr;
+
+use super::helpers;
+
+const STYLE_SUBJECTS: &str = "subjects";
+const STYLE_BODIES: &str = "bodies";
+const STYLE_CONVENTIONAL: &str = "conventional";
+
+pub const POSSIBLE_STYLES: [&str; 3] = [STYLE_SUBJECTS, STYLE_BODIES, STYLE_CONVENTIONAL];
+
+/// Sections of conventional commits summary by commit type, in order of output.
+/// Commits of other types and not conventional commits go to `Other changes`
+const SECTIONS: [(&str, &[&str]); 7] = [
+  ("Features", &["feat"]),
+  ("Fixes", &["fix"]),
+  ("Performance", &["perf"]),
+  ("Refactoring", &["refactor"]),
+  ("Documentation", &["docs"]),
+  ("Tests", &["test"]),
+  ("Chores", &["chore", "build", "ci", "style"]),
+];
+const OTHER_SECTION: &str = "Other changes";
+
+/// How description of merge request is built from its commits
+#[derive(Copy, Clone, PartialEq)]
+pub enum DescStyle {
+  /// Bullet list of subjects
+  Subjects,
+  /// Subjects as headings with full bodies
+  Bodies,
+  /// Subjects of conventional commits grouped by type: features, fixes, chores...
+  Conventional,
+}
+
+impl FromStr for DescStyle {
+  type Err = GLApiError;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      STYLE_SUBJECTS => Ok(DescStyle::Subjects),
+      STYLE_BODIES => Ok(DescStyle::Bodies),
+      STYLE_CONVENTIONAL => Ok(DescStyle::Conventional),
+      _ => Err(GLApiError::ParseError(format!(
+        "unknown style of description `{}`, expected one of: {}",
+        s,
+        POSSIBLE_STYLES.join(", ")
+      ))),
+    }
+  }
+}
+
+pub struct CommitMessage {
+  pub subject: String,
+  pub body: String,
+}
+
+/// Messages of commits of `source` that are not in `target`, oldest first
+pub fn branch_messages(remote: &str, target: &str, source: &str) -> Vec<CommitMessage> {
+  // commits are separated with RS and subject from body with US characters
+  helpers::get_branch_log(remote, target, source, "%s%x1f%b%x1e")
+    .split('\x1e')
+    .filter_map(|c| {
+      let (subject, body) = c.trim_start_matches('\n').split_once('\x1f')?;
+      Some(CommitMessage {
+        subject: subject.trim().to_owned(),
+        body: body.trim().to_owned(),
+      })
+    })
+    .collect()
+}
+
+/// Description of merge request made of commit messages
+pub fn describe(commits: &[CommitMessage], style: DescStyle) -> String {
+  match style {
+    DescStyle::Subjects => commits
+      .iter()
+      .map(|c| format!("- {}", c.subject))
+      .collect::<Vec<String>>()
+      .join("\n"),
+    DescStyle::Bodies => commits
+      .iter()
+      .map(|c| {
+        if c.body.is_empty() {
+          format!("### {}", c.subject)
+        } else {
+          format!("### {}\n\n{}", c.subject, c.body)
+        }
+      })
+      .collect::<Vec<String>>()
+      .join("\n\n"),
+    DescStyle::Conventional => conventional_summary(commits),
+  }
+}
+
+/// Conventional commit subject `type(scope)!: description` split to its parts
+struct Conventional<'a> {
+  kind: &'a str,
+  scope: Option<&'a str>,
+  breaking: bool,
+  description: &'a str,
+}
+
+fn parse_conventional(subject: &str) -> Option<Conventional<'_>> {
+  let (head, description) = subject.split_once(": ")?;
+  let (head, breaking) = match head.strip_suffix('!') {
+    Some(h) => (h, true),
+    None => (head, false),
+  };
+  let (kind, scope) = match head.split_once('(') {
+    Some((k, rest)) => (k, Some(rest.strip_suffix(')')?)),
+    None => (head, None),
+  };
+  if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+    return None;
+  }
+  Some(Conventional {
+    kind,
+    scope,
+    breaking,
+    description: description.trim(),
+  })
+}
+
+fn conventional_summary(commits: &[CommitMessage]) -> String {
+  let mut sections: Vec<(&str, Vec<String>)> = SECTIONS
+    .iter()
+    .map(|(title, _)| (*title, Vec::new()))
+    .chain(std::iter::once((OTHER_SECTION, Vec::new())))
+    .collect();
+
+  for c in commits {
+    let (pos, item) = match parse_conventional(&c.subject) {
+      Some(conv) => {
+        let kind = conv.kind.to_lowercase();
+        let pos = SECTIONS
+          .iter()
+          .position(|(_, kinds)| kinds.contains(&kind.as_str()))
+          .unwrap_or(SECTIONS.len());
+        let mut item = String::new();
+        if conv.breaking || c.body.contains("BREAKING CHANGE:") {
+          item.push_str("**BREAKING** ");
+        }
+        if let Some(scope) = conv.scope {
+          item.push_str(&format!("**{}:** ", scope));
+        }
+        item.push_str(conv.description);
+        (pos, item)
+      }
+      None => (SECTIONS.len(), c.subject.clone()),
+    };
+    sections[pos].1.push(format!("- {}", item));
+  }
+
+  sections
+    .iter()
+    .filter(|(_, items)| !items.is_empty())
+    .map(|(title, items)| format!("### {}\n\n{}", title, items.join("\n")))
+    .collect::<Vec<String>>()
+    .join("\n\n")
+}
@@ -1,15 +1,22 @@
 use directories::BaseDirs;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::Path;
 
-use crate::api::{GLApiError, GLApiResult};
+use crate::api::{GLApiError, GLApiResult, OAuthToken};
+use crate::secrets::{self, Secrets, TokenStore};
 
 static GLOBAL_FILE_NAME: &str = ".mergereq-config";
 static LOCAL_FILE_NAME: &str = ".mergereqrc.toml";
+static SECRETS_FILE_SUFFIX: &str = "-secrets";
+
+/// Key of the `hosts` entry used when there is no entry for the current host
+pub static ANY_HOST: &str = "*";
 
 pub enum CfgVariant {
   Global,
-  #[allow(dead_code)]
   Local,
 }
 
@@ -34,15 +41,167 @@ fn default_local_file_path() -> GLApiResult<String> {
   Ok(path)
 }
 
-#[derive(Serialize, Deserialize)]
+/// Extracts `host[:port]` from URL of GitLab to use it as a key of `hosts` map.
+/// Plain host is returned as is
+pub fn host_key(url: &str) -> String {
+  let without_scheme = match url.find("://") {
+    Some(i) => &url[i + 3..],
+    None => url,
+  };
+  let authority = without_scheme.split('/').next().unwrap_or_default();
+  let host = authority.rsplit('@').next().unwrap_or_default();
+  host.to_lowercase()
+}
+
+/// Settings from environment variables. Empty variables are ignored
+#[derive(Default)]
+pub struct EnvVars {
+  /// `MERGEREQ_TOKEN`
+  pub token: Option<String>,
+  /// `MERGEREQ_REPO_URL`
+  pub repo_url: Option<String>,
+  /// `MERGEREQ_PROJECT`
+  pub project: Option<String>,
+  /// `CI_JOB_TOKEN`, set by GitLab CI
+  pub ci_job_token: Option<String>,
+  /// `CI_SERVER_URL`, set by GitLab CI
+  pub ci_server_url: Option<String>,
+  /// `CI_PROJECT_PATH`, set by GitLab CI
+  pub ci_project_path: Option<String>,
+  /// `MERGEREQ_IDENTITY_PASSWORD`
+  pub identity_password: Option<String>,
+  /// `https_proxy` or `HTTPS_PROXY`
+  pub https_proxy: Option<String>,
+  /// `http_proxy` or `HTTP_PROXY`
+  pub http_proxy: Option<String>,
+  /// `no_proxy` or `NO_PROXY`
+  pub no_proxy: Option<String>,
+  /// `MERGEREQ_CACHE_DIR`
+  pub cache_dir: Option<String>,
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+  std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+impl EnvVars {
+  pub fn read() -> Self {
+    EnvVars {
+      token: non_empty_var("MERGEREQ_TOKEN"),
+      repo_url: non_empty_var("MERGEREQ_REPO_URL"),
+      project: non_empty_var("MERGEREQ_PROJECT"),
+      ci_job_token: non_empty_var("CI_JOB_TOKEN"),
+      ci_server_url: non_empty_var("CI_SERVER_URL"),
+      ci_project_path: non_empty_var("CI_PROJECT_PATH"),
+      identity_password: non_empty_var("MERGEREQ_IDENTITY_PASSWORD"),
+      https_proxy: non_empty_var("https_proxy").or_else(|| non_empty_var("HTTPS_PROXY")),
+      http_proxy: non_empty_var("http_proxy").or_else(|| non_empty_var("HTTP_PROXY")),
+      no_proxy: non_empty_var("no_proxy").or_else(|| non_empty_var("NO_PROXY")),
+      cache_dir: non_empty_var("MERGEREQ_CACHE_DIR"),
+    }
+  }
+}
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct GlobalData {
-  pub private_token: String,
+  /// Token from the single-host config of previous versions.
+  /// It is moved to `hosts` as `*` entry when config is read
+  #[serde(default, skip_serializing)]
+  private_token: Option<String>,
+  /// Credentials and settings of GitLab hosts, keyed by `host[:port]`
+  #[serde(default)]
+  pub hosts: BTreeMap<String, HostData>,
+  /// Maps SSH hosts of git remotes to URLs of GitLab,
+  /// for instances where they differ
+  #[serde(default)]
+  pub ssh_hosts: HashMap<String, String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct HostData {
+  /// Token in plain text. Used only with `plain` token store
+  pub private_token: Option<String>,
+  /// Reference to token kept outside of config, e.g. `keyring:gitlab.com`
+  pub token_ref: Option<String>,
+  /// Version of GitLab API. Default is `v4`
+  pub api_version: Option<String>,
+  /// Path of PEM file (or bundle) with additional root certificates
+  pub ca_cert: Option<String>,
+  /// Path of PKCS#12 file with client certificate and key for mutual TLS
+  pub client_identity: Option<String>,
+  /// Password of `client_identity`. `MERGEREQ_IDENTITY_PASSWORD` can be used instead
+  pub client_identity_password: Option<String>,
+  /// Disables TLS certificate validation. Dangerous
+  pub accept_invalid_certs: Option<bool>,
+  /// Timeout of establishing connection, in seconds
+  pub connect_timeout: Option<u64>,
+  /// Timeout of the whole request, in seconds. Default is 30
+  pub timeout: Option<u64>,
+  /// URL of HTTP(S) proxy
+  pub proxy: Option<String>,
+  /// Comma separated hosts that are requested without proxy
+  pub no_proxy: Option<String>,
+  /// Count of retries of failed requests. Default is 3
+  pub retries: Option<u32>,
+  /// Max count of requests a single command may send
+  pub request_budget: Option<u32>,
+  /// Commands warn when the private token expires within this count of days.
+  /// Default is 7, `0` disables the check
+  pub token_expiry_warning_days: Option<u32>,
+  /// ID of OAuth application used by `login`
+  pub oauth_client_id: Option<String>,
+  /// Reference to tokens of `login` kept outside of config
+  pub oauth_token_ref: Option<String>,
+  /// Tokens of `login` in plain text. Used only with `plain` token store.
+  /// It is the last field, as TOML tables go after values
+  pub oauth_token: Option<OAuthToken>,
+
+  #[serde(skip)]
+  resolved_token: OnceCell<Option<String>>,
+  #[serde(skip)]
+  resolved_oauth_token: OnceCell<Option<OAuthToken>>,
+}
+
+impl HostData {
+  /// Kind of storage the token of host is kept in
+  pub fn token_store(&self) -> Option<TokenStore> {
+    if let Some(r) = &self.token_ref {
+      secrets::parse_token_ref(r).ok().map(|(store, _)| store)
+    } else if self.private_token.is_some() {
+      Some(TokenStore::Plain)
+    } else {
+      None
+    }
+  }
+
+  /// Kind of storage the tokens of `login` are kept in
+  pub fn oauth_token_store(&self) -> Option<TokenStore> {
+    if let Some(r) = &self.oauth_token_ref {
+      secrets::parse_token_ref(r).ok().map(|(store, _)| store)
+    } else if self.oauth_token.is_some() {
+      Some(TokenStore::Plain)
+    } else {
+      None
+    }
+  }
+}
+
+/// Account of tokens of `login` in secret store
+fn oauth_account(host: &str) -> String {
+  format!("{}/oauth", host)
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct LocalData {
-  pub repo_url: String,
+  pub repo_url: Option<String>,
   pub default_project: Option<String>,
+  /// Name of git remote to infer `repo_url` and project from. Default is `origin`
+  pub remote: Option<String>,
+  /// Template of description used by `create mr` without `--template` and `--description`
+  pub mr_template: Option<String>,
+  /// Style of description generated from commits by `create mr` without `--description`
+  /// and templates: `subjects`, `bodies` or `conventional`
+  pub desc_from_commits: Option<String>,
 }
 
 pub struct Configs {
@@ -51,6 +210,7 @@ pub struct Configs {
 
   global_file_path: String,
   local_file_path: String,
+  secrets: Secrets,
 }
 
 impl Configs {
@@ -71,16 +231,54 @@ impl Configs {
       (Configs::parse_from_file(&path).ok(), path)
     };
 
-    Ok(Configs {
+    let mut configs = Configs {
+      secrets: Secrets::new(format!("{}{}", gpath, SECRETS_FILE_SUFFIX)),
       global_file_path: gpath,
       local_file_path: lpath,
       global,
       local,
-    })
+    };
+    configs.migrate_global()?;
+    configs.check_global_permissions();
+    Ok(configs)
+  }
+
+  /// Restricts access to global config if it still has plain text tokens
+  fn check_global_permissions(&self) {
+    let has_plain = self.global.as_ref().is_some_and(|g| {
+      g.hosts
+        .values()
+        .any(|h| h.private_token.is_some() || h.oauth_token.is_some())
+    });
+    if has_plain && restrict_permissions(&self.global_file_path).unwrap_or(false) {
+      warn!(
+        "Global config contains plain text tokens and was readable by other users. \
+         Its permissions are changed to 0600 ({})",
+        self.global_file_path
+      );
+    }
+  }
+
+  /// Moves token of the single-host config to the `*` host entry and rewrites the config file
+  fn migrate_global(&mut self) -> GLApiResult<()> {
+    let legacy_token = self.global.as_mut().and_then(|g| g.private_token.take());
+    if let Some(token) = legacy_token {
+      let global = self.global.as_mut().unwrap();
+      let host = global.hosts.entry(ANY_HOST.to_owned()).or_default();
+      if host.private_token.is_none() {
+        host.private_token = Some(token);
+      }
+      self.store(CfgVariant::Global)?;
+      eprintln!(
+        "Global config is migrated to the multi-host format ({})",
+        self.global_file_path
+      );
+    }
+    Ok(())
   }
 
   fn parse_from_file<T: DeserializeOwned>(path: &str) -> GLApiResult<T> {
-    let cfg_str = fs::read_to_string(&path)?;
+    let cfg_str = fs::read_to_string(path)?;
     let data = toml::from_str(&cfg_str)?;
     Ok(data)
   }
@@ -97,33 +295,288 @@ impl Configs {
       CfgVariant::Global => toml::ser::to_vec(&self.global)?,
       CfgVariant::Local => toml::ser::to_vec(&self.local)?,
     };
-    fs::write(self.get_file_path(variant), data_bytes)?;
-    Ok(())
+    match variant {
+      CfgVariant::Global => write_private(self.get_file_path(variant), &data_bytes),
+      CfgVariant::Local => Ok(fs::write(self.get_file_path(variant), data_bytes)?),
+    }
   }
 
-  pub fn save_new_token(&mut self, token: &str) -> GLApiResult<()> {
-    if self.global.is_none() {
-      self.global = Some(GlobalData {
-        private_token: String::new(),
-      });
+  /// Returns token of host, reading it from secret store if needed.
+  /// Failure to read it is reported as warning, so that commands not
+  /// requiring token still work
+  pub fn host_token<'a>(&self, host_data: &'a HostData) -> Option<&'a str> {
+    host_data
+      .resolved_token
+      .get_or_init(|| match &host_data.token_ref {
+        Some(r) => match self.secrets.load(r) {
+          Ok(token) => Some(token),
+          Err(e) => {
+            warn!("Cannot read private token `{}`: {}", r, e);
+            None
+          }
+        },
+        None => host_data.private_token.clone(),
+      })
+      .as_deref()
+  }
+
+  /// Tokens of `login` for host, reading them from secret store if needed.
+  /// Failure to read them is reported as warning
+  pub fn host_oauth_token<'a>(&self, host_data: &'a HostData) -> Option<&'a OAuthToken> {
+    host_data
+      .resolved_oauth_token
+      .get_or_init(|| match &host_data.oauth_token_ref {
+        Some(r) => match self
+          .secrets
+          .load(r)
+          .and_then(|t| Ok(serde_json::from_str(&t)?))
+        {
+          Ok(token) => Some(token),
+          Err(e) => {
+            warn!("Cannot read OAuth tokens `{}`: {}", r, e);
+            None
+          }
+        },
+        None => host_data.oauth_token.clone(),
+      })
+      .as_ref()
+  }
+
+  /// Saves secret to the given store and returns the store actually used with reference
+  /// to the secret (`None` for plain store).
+  /// When OS secret service is unavailable, encrypted file is used if its passphrase is set
+  fn save_secret(
+    &self,
+    store: TokenStore,
+    account: &str,
+    secret: &str,
+  ) -> GLApiResult<(TokenStore, Option<String>)> {
+    let saved = match store {
+      TokenStore::Plain => (store, None),
+      TokenStore::Keyring => match self.secrets.save(store, account, secret) {
+        Ok(r) => (store, Some(r)),
+        Err(e) if std::env::var_os(secrets::PASSPHRASE_ENV).is_some() => {
+          warn!("{}. Token is saved to encrypted file instead", e);
+          let r = self.secrets.save(TokenStore::File, account, secret)?;
+          (TokenStore::File, Some(r))
+        }
+        Err(e) => {
+          let hint = format!(
+            "{}. Set `{}` to use encrypted file or pass `--store plain`",
+            e,
+            secrets::PASSPHRASE_ENV
+          );
+          return Err(GLApiError::SecretStore(hint).into());
+        }
+      },
+      TokenStore::File => (store, Some(self.secrets.save(store, account, secret)?)),
+    };
+    Ok(saved)
+  }
+
+  /// Saves token of host to the given store and returns the store actually used.
+  /// Tokens of `login` are removed, as the host has a single credential
+  pub fn save_new_token(
+    &mut self,
+    host: &str,
+    token: &str,
+    store: TokenStore,
+  ) -> GLApiResult<TokenStore> {
+    let (store, token_ref) = self.save_secret(store, host, token)?;
+
+    let global = self.global.get_or_insert_with(GlobalData::default);
+    let host_data = global.hosts.entry(host.to_owned()).or_default();
+    let old_ref = host_data.token_ref.take();
+    let old_oauth_ref = host_data.oauth_token_ref.take();
+    host_data.oauth_token = None;
+    if store == TokenStore::Plain {
+      host_data.private_token = Some(token.to_owned());
+    } else {
+      host_data.private_token = None;
+      host_data.token_ref = token_ref.clone();
     }
+    self.store(CfgVariant::Global)?;
 
-    let global = self.global.as_mut().unwrap();
-    global.set_token(token);
-    self.store(CfgVariant::Global)
+    if let Some(old) = old_ref.filter(|old| Some(old) != token_ref.as_ref()) {
+      self.delete_secret(&old);
+    }
+    if let Some(old) = old_oauth_ref {
+      self.delete_secret(&old);
+    }
+    Ok(store)
+  }
+
+  /// Saves tokens of `login` to the given store and returns the store actually used.
+  /// Private token of the host is removed, as the host has a single credential
+  pub fn save_oauth_token(
+    &mut self,
+    host: &str,
+    client_id: &str,
+    token: &OAuthToken,
+    store: TokenStore,
+  ) -> GLApiResult<TokenStore> {
+    let serialized = serde_json::to_string(token)?;
+    let (store, token_ref) = self.save_secret(store, &oauth_account(host), &serialized)?;
+
+    let global = self.global.get_or_insert_with(GlobalData::default);
+    let host_data = global.hosts.entry(host.to_owned()).or_default();
+    let old_ref = host_data.token_ref.take();
+    host_data.private_token = None;
+    host_data.oauth_client_id = Some(client_id.to_owned());
+    if store == TokenStore::Plain {
+      host_data.oauth_token = Some(token.clone());
+      host_data.oauth_token_ref = None;
+    } else {
+      host_data.oauth_token = None;
+      host_data.oauth_token_ref = token_ref;
+    }
+    self.store(CfgVariant::Global)?;
+
+    if let Some(old) = old_ref {
+      self.delete_secret(&old);
+    }
+    Ok(store)
+  }
+
+  /// Removes tokens of `login` of host and returns them, so that they can be revoked
+  pub fn remove_oauth_token(&mut self, host: &str) -> GLApiResult<Option<OAuthToken>> {
+    let token = match self.global.as_ref().and_then(|g| g.hosts.get(host)) {
+      Some(h) => self.host_oauth_token(h).cloned(),
+      None => return Ok(None),
+    };
+    let host_data = self.global.as_mut().and_then(|g| g.hosts.get_mut(host));
+    let old_ref = host_data.and_then(|h| {
+      h.oauth_token = None;
+      h.oauth_token_ref.take()
+    });
+    self.store(CfgVariant::Global)?;
+    if let Some(old) = old_ref {
+      self.delete_secret(&old);
+    }
+    Ok(token)
+  }
+
+  pub fn remove_host(&mut self, host: &str) -> GLApiResult<bool> {
+    let removed = match self.global.as_mut() {
+      Some(g) => g.hosts.remove(host),
+      None => None,
+    };
+    if let Some(host_data) = &removed {
+      self.store(CfgVariant::Global)?;
+      for r in [&host_data.token_ref, &host_data.oauth_token_ref]
+        .iter()
+        .copied()
+        .flatten()
+      {
+        self.delete_secret(r);
+      }
+    }
+    Ok(removed.is_some())
   }
 
   pub fn remove_global_cfg(&mut self) -> GLApiResult<()> {
+    if let Some(g) = &self.global {
+      let refs = g
+        .hosts
+        .values()
+        .flat_map(|h| h.token_ref.iter().chain(h.oauth_token_ref.iter()));
+      for r in refs {
+        self.delete_secret(r);
+      }
+    }
     let path = self.get_file_path(CfgVariant::Global);
     fs::remove_file(path)?;
     Ok(())
   }
+
+  fn delete_secret(&self, token_ref: &str) {
+    if let Err(e) = self.secrets.delete(token_ref) {
+      warn!("Cannot delete private token `{}`: {}", token_ref, e);
+    }
+  }
 }
 
 impl GlobalData {
-  fn set_token(&mut self, token: &str) {
-    self.private_token = token.to_owned();
+  /// Finds settings of the GitLab host which `repo_url` points to,
+  /// falling back to the `*` entry
+  pub fn host_for_url(&self, repo_url: &str) -> Option<&HostData> {
+    self.host_entry_for_url(repo_url).map(|(_, h)| h)
+  }
+
+  /// Finds settings of exactly the host which `repo_url` points to, without the `*` fallback
+  pub fn exact_host_for_url(&self, repo_url: &str) -> Option<&HostData> {
+    self.hosts.get(&host_key(repo_url))
+  }
+
+  /// The same as `host_for_url`, with the key of the entry
+  pub fn host_entry_for_url(&self, repo_url: &str) -> Option<(&str, &HostData)> {
+    self
+      .hosts
+      .get_key_value(&host_key(repo_url))
+      .or_else(|| self.hosts.get_key_value(ANY_HOST))
+      .map(|(k, h)| (k.as_str(), h))
+  }
+}
+
+/// Writes file that only its owner can read
+pub fn write_private<P: AsRef<Path>>(path: P, data: &[u8]) -> GLApiResult<()> {
+  let path = path.as_ref();
+  #[cfg(unix)]
+  {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new()
+      .write(true)
+      .create(true)
+      .truncate(true)
+      .mode(0o600)
+      .open(path)?;
+    restrict_permissions(path)?;
+    file.write_all(data)?;
+  }
+  #[cfg(not(unix))]
+  fs::write(path, data)?;
+  Ok(())
+}
+
+/// Creates directory (with its parents) that only its owner can access
+pub fn create_private_dir<P: AsRef<Path>>(path: P) -> GLApiResult<()> {
+  let path = path.as_ref();
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    fs::DirBuilder::new()
+      .recursive(true)
+      .mode(0o700)
+      .create(path)?;
+    let mut perms = fs::metadata(path)?.permissions();
+    if perms.mode() & 0o077 != 0 {
+      perms.set_mode(0o700);
+      fs::set_permissions(path, perms)?;
+    }
   }
+  #[cfg(not(unix))]
+  fs::create_dir_all(path)?;
+  Ok(())
+}
+
+/// Sets 0600 permissions to file. Returns `true` if they were wider
+#[cfg(unix)]
+fn restrict_permissions<P: AsRef<Path>>(path: P) -> GLApiResult<bool> {
+  let path = path.as_ref();
+  use std::os::unix::fs::PermissionsExt;
+  let mut perms = fs::metadata(path)?.permissions();
+  if perms.mode() & 0o077 == 0 {
+    return Ok(false);
+  }
+  perms.set_mode(0o600);
+  fs::set_permissions(path, perms)?;
+  Ok(true)
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions<P: AsRef<Path>>(_path: P) -> GLApiResult<bool> {
+  Ok(false)
 }
 
 // #[test]
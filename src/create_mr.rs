@@ -1,7 +1,14 @@
-use super::helpers;
-use crate::api::{CreateMRBody, GLApi, GetUsersQuery, MergeRequest, UserState};
+use super::args::ArgName;
+use super::commit_summary::{self, DescStyle};
+use super::push::{self, BranchState, PushMode};
+use super::{editor, helpers, ls, mr_template};
+use crate::api::{
+  CreateMRBody, GLApi, GLApiResult, GetMergeRequestsQuery, GetUsersQuery, MRState, MergeRequest,
+  Pagination, UpdateMRBody, UserState,
+};
+use crate::output::OutputFormat;
 use clap::ArgMatches;
-use std::io::{stdin, stdout, Write};
+use mergereq::configs::LocalData;
 
 pub fn fill_mr_create_data<'a>(
   glapi: &GLApi,
@@ -46,22 +53,341 @@ pub fn fill_mr_create_data<'a>(
   }
 }
 
-fn get_assignee_id<'a>(
-  glapi: &'a GLApi,
+/// Where description of merge request comes from when it is not given
+pub enum DescriptionSource<'a> {
+  Template(&'a str),
+  Commits(DescStyle),
+}
+
+/// Source of description from `--template` or `--desc-from-commits`, then from
+/// `mr_template` or `desc_from_commits` of local config, in this order
+pub fn description_source<'a>(
+  args_matches: &'a ArgMatches,
+  local: Option<&'a LocalData>,
+) -> GLApiResult<Option<DescriptionSource<'a>>> {
+  if let Some(name) = args_matches.value_of(ArgName::Template) {
+    return Ok(Some(DescriptionSource::Template(name)));
+  }
+  if let Some(style) = args_matches.value_of(ArgName::DescFromCommits) {
+    return Ok(Some(DescriptionSource::Commits(style.parse()?)));
+  }
+  let local = match local {
+    Some(l) => l,
+    None => return Ok(None),
+  };
+  if let Some(name) = local.mr_template.as_deref() {
+    return Ok(Some(DescriptionSource::Template(name)));
+  }
+  match local.desc_from_commits.as_deref() {
+    Some(style) => Ok(Some(DescriptionSource::Commits(style.parse()?))),
+    None => Ok(None),
+  }
+}
+
+/// Description of merge request from template or commits of the source branch.
+/// `None` if there is no source, or the branch has no commits to describe
+pub fn generate_description(
+  glapi: &GLApi,
+  project: &str,
+  mr_data: &CreateMRBody,
+  source: Option<DescriptionSource>,
+  remote: &str,
+) -> GLApiResult<Option<String>> {
+  match source {
+    Some(DescriptionSource::Template(name)) => {
+      description_from_template(glapi, project, mr_data, name, remote).map(Some)
+    }
+    Some(DescriptionSource::Commits(style)) => {
+      let commits =
+        commit_summary::branch_messages(remote, &mr_data.target_branch, &mr_data.source_branch);
+      if commits.is_empty() {
+        log::warn!(
+          "No commits of `{}` are found that are not in `{}`, description is left empty",
+          mr_data.source_branch,
+          mr_data.target_branch
+        );
+        return Ok(None);
+      }
+      Ok(Some(commit_summary::describe(&commits, style)))
+    }
+    None => Ok(None),
+  }
+}
+
+/// Description of merge request rendered from template with its branches, title,
+/// commits and issue linked by the source branch
+pub fn description_from_template(
+  glapi: &GLApi,
+  project: &str,
+  mr_data: &CreateMRBody,
+  template: &str,
+  remote: &str,
+) -> GLApiResult<String> {
+  let text = mr_template::load(glapi, project, template, &mr_data.target_branch)?;
+  let vars = mr_template::TemplateVars {
+    source_branch: &mr_data.source_branch,
+    target_branch: &mr_data.target_branch,
+    title: &mr_data.title,
+    commits: helpers::get_branch_commits(
+      remote,
+      &mr_data.target_branch,
+      &mr_data.source_branch,
+      "%s",
+    ),
+    issue: mr_template::issue_from_branch(&mr_data.source_branch),
+  };
+  Ok(mr_template::render(&text, &vars))
+}
+
+/// Open merge request from the source into the target branch. GitLab allows only one of them,
+/// so creating another one fails with `409 Conflict`
+pub fn find_open_mr(
+  glapi: &GLApi,
+  project: &str,
+  mr_data: &CreateMRBody,
+) -> GLApiResult<Option<MergeRequest>> {
+  let query = GetMergeRequestsQuery::new()
+    .state(MRState::Opened)
+    .source_branch(&mr_data.source_branch)
+    .target_branch(&mr_data.target_branch);
+  let mrs = glapi.get_project_merge_requests(Some(project), &query, &Pagination::new().limit(1))?;
+  Ok(mrs.into_iter().next())
+}
+
+pub fn log_existing_mr(mr: &MergeRequest) {
+  eprintln!(
+    "Merge request !{} ({}) from `{}` into `{}` is already open:",
+    mr.iid,
+    helpers::get_one_line(&mr.title),
+    mr.source_branch,
+    mr.target_branch
+  );
+  eprintln!("{}", mr.web_url);
+}
+
+/// Changes of `existing` merge request that make it match the new one. Title is changed
+/// only if it is given with `--title` or composed in editor, as by default it is the message
+/// of the last commit. Flags are only set, never cleared
+pub fn update_existing_data<'a>(
+  existing: &MergeRequest,
+  mr_data: &'a CreateMRBody,
+  args_matches: &ArgMatches,
+) -> UpdateMRBody<'a> {
+  let explicit_title = args_matches.is_present("title") || args_matches.is_present(ArgName::Edit);
+  let set_flag = |new: Option<bool>, old: Option<bool>| {
+    Some(true).filter(|_| new == Some(true) && old != Some(true))
+  };
+  UpdateMRBody {
+    title: Some(mr_data.title.as_str()).filter(|t| explicit_title && *t != existing.title),
+    description: mr_data
+      .description
+      .filter(|d| existing.description.as_deref() != Some(*d)),
+    assignee_ids: mr_data
+      .assignee_id
+      .filter(|id| !existing.assignees.iter().any(|u| u.id == *id))
+      .map(|id| vec![id]),
+    remove_source_branch: set_flag(
+      mr_data.remove_source_branch,
+      existing.should_remove_source_branch,
+    ),
+    squash: set_flag(mr_data.squash, existing.squash),
+    ..UpdateMRBody::default()
+  }
+}
+
+/// Title and description written in editor
+pub struct EditedMr {
+  pub title: String,
+  pub description: Option<String>,
+}
+
+/// Opens editor on title and description of merge request, followed by its branches
+/// and commits below the scissors line, like `git commit --verbose` does.
+/// The first line of the saved text is the title, the following lines are the description
+pub fn edit_mr(mr_data: &CreateMRBody, remote: &str) -> GLApiResult<EditedMr> {
+  let mut buf = format!("{}\n\n", mr_data.title.trim_end());
+  if let Some(d) = mr_data.description {
+    buf.push_str(d.trim_end());
+    buf.push('\n');
+  }
+  buf.push_str(&format!(
+    "\n{}\n\
+     # Do not modify or remove the line above. Everything below it is ignored.\n\
+     # The first line is the title of merge request, the following lines are its description.\n\
+     # Empty title cancels creation.\n\
+     #\n\
+     # Source branch: {}\n\
+     # Target branch: {}\n",
+    editor::SCISSORS,
+    mr_data.source_branch,
+    mr_data.target_branch
+  ));
+  let commits = helpers::get_branch_commits(
+    remote,
+    &mr_data.target_branch,
+    &mr_data.source_branch,
+    "%h %s",
+  );
+  if !commits.is_empty() {
+    buf.push_str(&format!("#\n# Commits ({}):\n", commits.len()));
+    for c in &commits {
+      buf.push_str(&format!("#   {}\n", c));
+    }
+  }
+
+  let text = editor::edit(&buf)?;
+  let text = editor::strip_help(&text).trim();
+  let (title, description) = text.split_once('\n').unwrap_or((text, ""));
+  let title = title.trim();
+  if title.is_empty() {
+    eprintln!("Title is empty. Canceling...");
+    std::process::exit(1);
+  }
+  let description = description.trim();
+  Ok(EditedMr {
+    title: title.to_owned(),
+    description: Some(description.to_owned()).filter(|d| !d.is_empty()),
+  })
+}
+
+/// How the source branch is pushed before the merge request is created
+pub struct PushSettings {
+  pub mode: PushMode,
+  /// Push options passed to `git push -o`
+  pub options: Vec<String>,
+  /// Creates the merge request with `merge_request.*` push options
+  pub create_on_push: bool,
+}
+
+impl PushSettings {
+  pub fn from_args(args_matches: &ArgMatches) -> Self {
+    let options: Vec<String> = args_matches
+      .values_of(ArgName::PushOption)
+      .map(|v| v.map(str::to_owned).collect())
+      .unwrap_or_default();
+    let create_on_push = args_matches.is_present(ArgName::CreateOnPush);
+    let mode = if args_matches.is_present(ArgName::NoPush) {
+      PushMode::Never
+    } else if args_matches.is_present(ArgName::Push) || create_on_push || !options.is_empty() {
+      PushMode::Always
+    } else {
+      PushMode::Ask
+    };
+    PushSettings {
+      mode,
+      options,
+      create_on_push,
+    }
+  }
+}
+
+/// Pushes the source branch if it is missing on the remote or behind local, asking first
+/// unless `--push` is given. Returns merge request if it is created with push options
+pub fn push_source_branch(
+  glapi: &GLApi,
+  project: &str,
+  mr_data: &CreateMRBody,
+  remote: &str,
+  settings: &PushSettings,
+) -> GLApiResult<Option<MergeRequest>> {
+  if settings.mode == PushMode::Never {
+    return Ok(None);
+  }
+  let branch = &mr_data.source_branch;
+  let state = push::branch_state(glapi, project, branch)?;
+  match state {
+    BranchState::Missing => eprintln!("Source branch `{}` is not pushed to `{}`", branch, remote),
+    BranchState::Behind(count) => eprintln!(
+      "Source branch `{}` on `{}` is behind local by {} commit(s)",
+      branch, remote, count
+    ),
+    BranchState::Diverged => log::warn!(
+      "Source branch `{}` on `{}` has diverged from local, it is left as is",
+      branch,
+      remote
+    ),
+    BranchState::NotLocal | BranchState::UpToDate => {}
+  }
+  if !state.needs_push() {
+    if settings.create_on_push {
+      log::info!("Nothing to push, merge request is created with API");
+    }
+    return Ok(None);
+  }
+  if settings.mode == PushMode::Ask
+    && !helpers::ask(&format!(
+      "Push it with `git push -u {} {}`?",
+      remote, branch
+    ))
+  {
+    return Ok(None);
+  }
+
+  let mut options = settings.options.clone();
+  if settings.create_on_push {
+    options.push("merge_request.create".to_owned());
+    options.push(format!("merge_request.target={}", mr_data.target_branch));
+    options.push(format!(
+      "merge_request.title={}",
+      helpers::get_one_line(&mr_data.title)
+    ));
+    if mr_data.remove_source_branch == Some(true) {
+      options.push("merge_request.remove_source_branch".to_owned());
+    }
+  }
+  push::push(remote, branch, &options)?;
+  if !settings.create_on_push {
+    return Ok(None);
+  }
+
+  let query = GetMergeRequestsQuery::new()
+    .state(MRState::Opened)
+    .source_branch(branch)
+    .target_branch(&mr_data.target_branch);
+  let mr = match glapi
+    .get_project_merge_requests(Some(project), &query, &Pagination::new().limit(1))?
+    .into_iter()
+    .next()
+  {
+    Some(mr) => mr,
+    None => {
+      log::warn!("Merge request is not created on push, creating it with API");
+      return Ok(None);
+    }
+  };
+  // push options cannot hold multiline description, assignee ID and squash flag
+  let update = UpdateMRBody {
+    description: mr_data.description,
+    assignee_ids: mr_data.assignee_id.map(|id| vec![id]),
+    squash: mr_data.squash.filter(|s| *s),
+    ..UpdateMRBody::default()
+  };
+  if update.is_empty() {
+    return Ok(Some(mr));
+  }
+  glapi
+    .update_merge_request(Some(project), mr.iid, &update)
+    .map(Some)
+}
+
+pub fn get_assignee_id(
+  glapi: &GLApi,
   assignee_id: Option<&str>,
   assignee_name: Option<&str>,
 ) -> Option<u32> {
   if let Some(name) = assignee_name {
     let uq = GetUsersQuery::new().username(name).state(UserState::Active);
 
-    let users = glapi.get_users(&uq).unwrap_or_else(|err| {
-      eprintln!(
-        "[ERROR] You specify assignee name, but users request failed. {}",
-        err
-      );
-      std::process::exit(1);
-    });
-    let user = users.get(0).unwrap_or_else(|| {
+    let users = glapi
+      .get_users(&uq, &Pagination::new().limit(1))
+      .unwrap_or_else(|err| {
+        eprintln!(
+          "[ERROR] You specify assignee name, but users request failed. {}",
+          err
+        );
+        std::process::exit(1);
+      });
+    let user = users.first().unwrap_or_else(|| {
       eprintln!("[ERROR] Cannot find user with name: `{}`", name);
       std::process::exit(1);
     });
@@ -92,36 +418,34 @@ fn get_assignee_str(id: Option<u32>, name: Option<&str>) -> String {
   }
 }
 
-fn prompt() -> bool {
-  print!("Do you want to continue? [Y/n]");
-  let mut s = String::new();
-  let _ = stdout().flush();
-  stdin()
-    .read_line(&mut s)
-    .expect("Did not enter a correct string");
-
-  let s = s.trim();
-
-  s == "" || s == "y" || s == "Y"
-}
-
 pub fn confirm_mr(mr_data: &CreateMRBody, args: &ArgMatches) {
-  println!("You creating merge requests with this parameters:");
-  println!("  Source branch: — {}", mr_data.source_branch);
-  println!("  Target branch: — {}", mr_data.target_branch);
+  eprintln!("You creating merge requests with this parameters:");
+  eprintln!("  Source branch: — {}", mr_data.source_branch);
+  eprintln!("  Target branch: — {}", mr_data.target_branch);
   let title = helpers::get_one_line(&mr_data.title);
-  println!("  Title branch:  — {}", title);
+  eprintln!("  Title branch:  — {}", title);
   let assignee = get_assignee_str(mr_data.assignee_id, args.value_of("assignee-name"));
-  println!("  Assignee:    —   {}", assignee);
+  eprintln!("  Assignee:    —   {}", assignee);
 
-  if !prompt() {
-    println!("Canceling...");
-    std::process::exit(1);
+  helpers::confirm_or_exit();
+}
+
+pub fn log_unchanged_mr(mr: &MergeRequest, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    return ls::single_mr(mr, fmt);
   }
+  println!("\nYour merge request is up to date. You can see it here:");
+  println!("{}", mr.web_url);
+  println!("Status: {}", mr.merge_status);
+  Ok(())
 }
 
-pub fn log_new_mr(mr: &MergeRequest) {
+pub fn log_new_mr(mr: &MergeRequest, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    return ls::single_mr(mr, fmt);
+  }
   println!("\nYour merge request is created. You can see it here:");
   println!("{}", mr.web_url);
   println!("Status: {}", mr.merge_status);
+  Ok(())
 }
@@ -0,0 +1,92 @@
+use mergereq::api::{self, GLApiError, GLApiResult};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Attempts to create temporary file with a new random name
+const CREATE_ATTEMPTS: u32 = 10;
+
+/// Line separating text of the user from the help below it, like in `git commit --verbose`
+pub const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// Editor from `VISUAL` or `EDITOR`, `vi` (`notepad` on Windows) if neither is set
+fn editor_cmd() -> String {
+  ["VISUAL", "EDITOR"]
+    .iter()
+    .filter_map(|var| env::var(var).ok())
+    .find(|e| !e.trim().is_empty())
+    .unwrap_or_else(|| {
+      if cfg!(target_os = "windows") {
+        "notepad".to_owned()
+      } else {
+        "vi".to_owned()
+      }
+    })
+}
+
+/// Opens editor on a temporary file with `initial` text and returns the saved text.
+/// Editor command may contain arguments, e.g. `code --wait`
+pub fn edit(initial: &str) -> GLApiResult<String> {
+  let path = create_temp_file(initial)?;
+
+  let editor = editor_cmd();
+  let status = if cfg!(target_os = "windows") {
+    Command::new("cmd")
+      .arg("/C")
+      .arg(format!("{} \"{}\"", editor, path.display()))
+      .status()
+  } else {
+    Command::new("sh")
+      .arg("-c")
+      .arg(format!("{} \"$@\"", editor))
+      .arg(&editor)
+      .arg(&path)
+      .status()
+  };
+
+  let result = match status {
+    Ok(s) if s.success() => fs::read_to_string(&path).map_err(Into::into),
+    Ok(s) => Err(GLApiError::Editor(format!("`{}` exited with {}", editor, s)).into()),
+    Err(e) => Err(GLApiError::Editor(format!("cannot run `{}`: {}", editor, e)).into()),
+  };
+  let _ = fs::remove_file(&path);
+  result
+}
+
+/// Creates a new file with `text` in temp dir, readable only by the user.
+/// The name is random and existing files are never opened, so a planted symlink is not followed
+fn create_temp_file(text: &str) -> GLApiResult<PathBuf> {
+  for _ in 0..CREATE_ATTEMPTS {
+    let suffix: String = api::random_string().chars().take(16).collect();
+    let path = env::temp_dir().join(format!("mergereq-{}.md", suffix));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::OpenOptionsExt;
+      options.mode(0o600);
+    }
+    match options.open(&path) {
+      Ok(mut file) => {
+        if let Err(e) = file.write_all(text.as_bytes()) {
+          let _ = fs::remove_file(&path);
+          return Err(e.into());
+        }
+        return Ok(path);
+      }
+      Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+      Err(e) => return Err(e.into()),
+    }
+  }
+  Err(GLApiError::Editor("cannot create temporary file".to_owned()).into())
+}
+
+/// Text above the scissors line
+pub fn strip_help(text: &str) -> &str {
+  match text.find(SCISSORS) {
+    Some(pos) => &text[..pos],
+    None => text,
+  }
+}
@@ -1,9 +1,10 @@
 use crate::api::GLApi;
+use std::io::{stderr, stdin, Write};
 use std::process::Command;
 
 fn exec_get_string(cmd: &str, err_msg: &str) -> String {
   let output = if cfg!(target_os = "windows") {
-    Command::new("cmd").args(&["/C", cmd]).output()
+    Command::new("cmd").args(["/C", cmd]).output()
   } else {
     Command::new("sh").arg("-c").arg(cmd).output()
   };
@@ -42,6 +43,35 @@ pub fn get_git_ref_msg(git_ref: &str) -> String {
   exec_get_string(&cmd, err_msg)
 }
 
+/// Output of `git log --format` of commits of `source` that are not in `target`, oldest first.
+/// Merge commits are skipped. `target` is looked up on `remote` first, as it may be not checked out locally
+pub fn get_branch_log(remote: &str, target: &str, source: &str, format: &str) -> String {
+  let remote_target = format!("{}/{}", remote, target);
+  [remote_target.as_str(), target]
+    .iter()
+    .find_map(|t| {
+      let output = Command::new("git")
+        .args(["log", "--reverse", "--no-merges"])
+        .arg(format!("--format={}", format))
+        .arg(format!("{}..{}", t, source))
+        .output()
+        .ok()?;
+      if !output.status.success() {
+        return None;
+      }
+      Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    })
+    .unwrap_or_default()
+}
+
+/// Commits of `source` that are not in `target`, one line per commit formatted with `format`
+pub fn get_branch_commits(remote: &str, target: &str, source: &str, format: &str) -> Vec<String> {
+  get_branch_log(remote, target, source, format)
+    .lines()
+    .map(str::to_owned)
+    .collect()
+}
+
 pub fn get_one_line(s: &str) -> String {
   let count = s.lines().count();
   if count < 2 {
@@ -50,3 +80,30 @@ pub fn get_one_line(s: &str) -> String {
     s.lines().next().unwrap_or_default().to_owned() + "..."
   }
 }
+
+/// Asks user for confirmation. Prompt is printed to stderr,
+/// so stdout stays clean for machine-readable output
+pub fn prompt() -> bool {
+  ask("Do you want to continue?")
+}
+
+/// Asks user a yes/no question, yes is the default
+pub fn ask(question: &str) -> bool {
+  eprint!("{} [Y/n]", question);
+  let mut s = String::new();
+  let _ = stderr().flush();
+  stdin()
+    .read_line(&mut s)
+    .expect("Did not enter a correct string");
+
+  let s = s.trim();
+
+  s.is_empty() || s == "y" || s == "Y"
+}
+
+pub fn confirm_or_exit() {
+  if !prompt() {
+    eprintln!("Canceling...");
+    std::process::exit(1);
+  }
+}
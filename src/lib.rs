@@ -0,0 +1,45 @@
+//! Client of GitLab API for merge requests, projects, branches and users,
+//! used by `mergereq` CLI.
+//!
+//! ```no_run
+//! use mergereq::api::{GLApi, GetMergeRequestsQuery, MRState, Pagination, ReqParams};
+//!
+//! # fn main() -> mergereq::api::GLApiResult<()> {
+//! let params = ReqParams::new()
+//!   .repo_url("https://gitlab.example.com")
+//!   .private_token("secret")
+//!   .default_project("group/project");
+//! let gl = GLApi::init(params);
+//! let query = GetMergeRequestsQuery::new().state(MRState::Opened);
+//! let mrs = gl.get_project_merge_requests(None, &query, &Pagination::new().limit(10))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Settings can also be resolved the same way the CLI does it,
+//! from environment variables, configs and git remote:
+//!
+//! ```no_run
+//! use mergereq::configs::{Configs, EnvVars};
+//! use mergereq::settings::{resolve_req_params, Overrides};
+//!
+//! # fn main() -> mergereq::api::GLApiResult<()> {
+//! let configs = Configs::read(None, None)?;
+//! let params = resolve_req_params(&configs, &EnvVars::read(), &Overrides::default());
+//! let gl = mergereq::api::GLApi::init(params);
+//! # Ok(())
+//! # }
+//! ```
+
+#[macro_use]
+extern crate failure;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde;
+
+pub mod api;
+pub mod configs;
+pub mod remote;
+pub mod secrets;
+pub mod settings;
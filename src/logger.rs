@@ -0,0 +1,77 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Target prefix of records of mergereq, records of dependencies are skipped
+const TARGET: &str = "mergereq";
+
+/// Writes records as `[LEVEL] message` to stderr, or with timestamps to log file.
+/// Warnings and errors are printed to stderr even when log file is used
+struct Logger {
+  level: LevelFilter,
+  file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= self.level && metadata.target().starts_with(TARGET)
+  }
+
+  fn log(&self, record: &Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+    if let Some(file) = &self.file {
+      let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+      if let Ok(mut f) = file.lock() {
+        let _ = writeln!(
+          f,
+          "{}.{:03} [{}] {}",
+          ts.as_secs(),
+          ts.subsec_millis(),
+          record.level(),
+          record.args()
+        );
+      }
+      if record.level() > Level::Warn {
+        return;
+      }
+    }
+    eprintln!("[{}] {}", record.level(), record.args());
+  }
+
+  fn flush(&self) {
+    if let Some(Ok(mut f)) = self.file.as_ref().map(Mutex::lock) {
+      let _ = f.flush();
+    }
+  }
+}
+
+/// Log level for count of `-v` flags: warnings by default,
+/// then requests and settings, headers, bodies
+pub fn level_for_verbosity(verbosity: u64) -> LevelFilter {
+  match verbosity {
+    0 => LevelFilter::Warn,
+    1 => LevelFilter::Info,
+    2 => LevelFilter::Debug,
+    _ => LevelFilter::Trace,
+  }
+}
+
+/// Installs logger, which appends to `log_file` if it is given
+pub fn init(level: LevelFilter, log_file: Option<&str>) -> io::Result<()> {
+  let file = match log_file {
+    Some(path) => Some(Mutex::new(
+      OpenOptions::new().create(true).append(true).open(path)?,
+    )),
+    None => None,
+  };
+  // logger is installed once at start, so it is never set already
+  let _ = log::set_boxed_logger(Box::new(Logger { level, file }));
+  log::set_max_level(level);
+  Ok(())
+}
@@ -0,0 +1,150 @@
+use mergereq::api::{random_string, GLApiError, GLApiResult, OAuthClient, OAuthToken, Pkce};
+use reqwest::Url;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Local port GitLab redirects browser to after authorization
+pub const DEFAULT_PORT: u16 = 7171;
+const CALLBACK_PATH: &str = "/callback";
+/// How long browser may take to send request after it connected
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the user may take to authorize mergereq in browser
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Authorization code flow with PKCE: the user authorizes application in browser,
+/// and GitLab redirects it with code to the local port
+pub fn with_browser(client: &OAuthClient, port: u16, scopes: &str) -> GLApiResult<OAuthToken> {
+  let listener = TcpListener::bind(("127.0.0.1", port))?;
+  let redirect_uri = format!(
+    "http://127.0.0.1:{}{}",
+    listener.local_addr()?.port(),
+    CALLBACK_PATH
+  );
+  let pkce = Pkce::new();
+  let state = random_string();
+  let url = client.authorize_url(&redirect_uri, scopes, &state, &pkce)?;
+
+  eprintln!("Open this page in browser and authorize mergereq:");
+  eprintln!("{}", url);
+  eprintln!("Waiting for redirect to {} ...", redirect_uri);
+  let code = wait_for_code(&listener, &state)?;
+  client.exchange_code(&code, &redirect_uri, &pkce)
+}
+
+/// Device authorization flow: the user enters the shown code on GitLab page
+pub fn with_device(client: &OAuthClient, scopes: &str) -> GLApiResult<OAuthToken> {
+  let auth = client.authorize_device(scopes)?;
+  eprintln!(
+    "Open {} and enter the code: {}",
+    auth.verification_uri, auth.user_code
+  );
+  if let Some(complete) = &auth.verification_uri_complete {
+    eprintln!("Or open {}", complete);
+  }
+  eprintln!("Waiting for authorization...");
+  client.poll_device_token(&auth)
+}
+
+/// Accepts connections until browser is redirected to callback with code.
+/// Every connection is served in its own thread, so that speculative connections
+/// of browser which never send a request do not block the others
+fn wait_for_code(listener: &TcpListener, state: &str) -> GLApiResult<String> {
+  let listener = listener.try_clone()?;
+  let state = state.to_owned();
+  let (tx, rx) = mpsc::channel();
+  thread::spawn(move || {
+    for stream in listener.incoming() {
+      let stream = match stream {
+        Ok(s) => s,
+        Err(_) => continue,
+      };
+      let (tx, state) = (tx.clone(), state.clone());
+      thread::spawn(move || {
+        if let Some(res) = handle_redirect(stream, &state) {
+          let _ = tx.send(res);
+        }
+      });
+    }
+  });
+  rx.recv_timeout(LOGIN_TIMEOUT).unwrap_or_else(|_| {
+    Err(
+      GLApiError::OAuth {
+        error: "timeout".to_owned(),
+        description: format!(
+          "redirect is not received in {} minutes",
+          LOGIN_TIMEOUT.as_secs() / 60
+        ),
+      }
+      .into(),
+    )
+  })
+}
+
+/// Answers request of browser. `None` if it is not a valid redirect, so waiting goes on
+fn handle_redirect(mut stream: TcpStream, state: &str) -> Option<GLApiResult<String>> {
+  stream.set_read_timeout(Some(READ_TIMEOUT)).ok()?;
+  let url = match request_url(&stream) {
+    Some(u) if u.path() == CALLBACK_PATH => u,
+    _ => {
+      respond(&mut stream, "404 Not Found", "Not found");
+      return None;
+    }
+  };
+  let param = |name: &str| {
+    url
+      .query_pairs()
+      .find(|(k, _)| k == name)
+      .map(|(_, v)| v.into_owned())
+  };
+
+  // e.g. an old page reloaded or a forged request, the right redirect may still come
+  if param("state").as_deref() != Some(state) {
+    respond(&mut stream, "400 Bad Request", "Unexpected state");
+    return None;
+  }
+  if let Some(error) = param("error") {
+    respond(&mut stream, "200 OK", "Authorization failed, see terminal");
+    let description = param("error_description").unwrap_or_else(|| error.clone());
+    return Some(Err(GLApiError::OAuth { error, description }.into()));
+  }
+  match param("code") {
+    Some(code) => {
+      respond(
+        &mut stream,
+        "200 OK",
+        "mergereq is authorized, you can close this page",
+      );
+      Some(Ok(code))
+    }
+    None => {
+      respond(&mut stream, "400 Bad Request", "Code is missing");
+      None
+    }
+  }
+}
+
+/// URL of HTTP request read from the stream. Headers are skipped
+fn request_url(stream: &TcpStream) -> Option<Url> {
+  let mut reader = BufReader::new(stream);
+  let mut request_line = String::new();
+  reader.read_line(&mut request_line).ok()?;
+  let mut header = String::new();
+  while reader.read_line(&mut header).ok()? > 2 {
+    header.clear();
+  }
+  let target = request_line.split_whitespace().nth(1)?;
+  Url::parse(&format!("http://127.0.0.1{}", target)).ok()
+}
+
+fn respond(stream: &mut TcpStream, status: &str, text: &str) {
+  let resp = format!(
+    "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    text.len(),
+    text
+  );
+  let _ = stream.write_all(resp.as_bytes());
+}
@@ -1,92 +1,573 @@
-use crate::api::{Branch, MergeRequest, Project, User};
+use crate::api::{
+  Branch, GLApiResult, MRApprovals, MergeRequest, PersonalAccessToken, Project, User,
+};
+use crate::configs::HostData;
+use crate::output::{self, OutputFormat, Records};
+use crate::secrets::TokenStore;
 use prettytable::Table;
+use std::collections::BTreeMap;
 
-pub fn users(users: &[User]) {
-  let mut table = Table::new();
-
-  table.add_row(row!["ID", "USERNAME", "NAME", "STATE"]);
+pub fn users(users: &[User], fmt: OutputFormat) -> GLApiResult<()> {
+  let mut records = Records::new(&[
+    ("ID", "id"),
+    ("USERNAME", "username"),
+    ("NAME", "name"),
+    ("STATE", "state"),
+  ]);
   for u in users {
-    table.add_row(row![u.id, u.username, u.name, u.state]);
+    records.push(vec![
+      u.id.to_string(),
+      u.username.clone(),
+      u.name.clone(),
+      u.state.clone(),
+    ]);
   }
-  println!("Users ({}):", users.len());
-  table.printstd();
-}
 
-pub fn projects(projects: &[Project]) {
-  let mut table = Table::new();
+  if fmt.is_table() {
+    println!("Users ({}):", users.len());
+  }
+  output::print(fmt, users, &records)
+}
 
-  table.add_row(row!["ID", "NAME", "DESC", "DEF_BRANCH"]);
+pub fn projects(projects: &[Project], fmt: OutputFormat) -> GLApiResult<()> {
+  let mut records = Records::new(&[
+    ("ID", "id"),
+    ("NAME", "name"),
+    ("DESC", "description"),
+    ("DEF_BRANCH", "default_branch"),
+  ]);
   for p in projects {
-    let desc: &str = p
-      .description
-      .as_ref()
-      .map(|s| s.as_str())
-      .unwrap_or_default();
-    table.add_row(row![p.id, p.name, desc, p.default_branch]);
-  }
-  println!("Projects ({}):", projects.len());
-  table.printstd();
-}
+    let desc: &str = p.description.as_deref().unwrap_or_default();
+    records.push(vec![
+      p.id.to_string(),
+      p.name.clone(),
+      desc.to_owned(),
+      p.default_branch.clone(),
+    ]);
+  }
 
-pub fn branches(branches: &[Branch]) {
-  let mut table = Table::new();
+  if fmt.is_table() {
+    println!("Projects ({}):", projects.len());
+  }
+  output::print(fmt, projects, &records)
+}
 
-  table.add_row(row!["SHA", "NAME", "AUTHOR"]);
+pub fn branches(branches: &[Branch], fmt: OutputFormat) -> GLApiResult<()> {
+  let mut records = Records::new(&[
+    ("SHA", "short_id"),
+    ("NAME", "name"),
+    ("AUTHOR", "author_name"),
+  ]);
   for b in branches {
-    table.add_row(row![b.commit.short_id, b.name, b.commit.author_name]);
+    records.push(vec![
+      b.commit.short_id.clone(),
+      b.name.clone(),
+      b.commit.author_name.clone(),
+    ]);
   }
-  println!("Branches ({}):", branches.len());
-  table.printstd();
+
+  if fmt.is_table() {
+    println!("Branches ({}):", branches.len());
+  }
+  output::print(fmt, branches, &records)
+}
+
+const MR_COLUMNS: [(&str, &str); 7] = [
+  ("ID", "id"),
+  ("IID", "iid"),
+  ("AUTHOR", "author"),
+  ("ASSIGNEE", "assignee"),
+  ("STATE", "state"),
+  ("SOURCE_BRANCH", "source_branch"),
+  ("TARGET_BRANCH", "target_branch"),
+];
+
+fn mr_row(mr: &MergeRequest) -> Vec<String> {
+  let assignee = if let Some(u) = &mr.assignee {
+    &u.username
+  } else {
+    ""
+  };
+
+  vec![
+    mr.id.to_string(),
+    mr.iid.to_string(),
+    mr.author.username.clone(),
+    assignee.to_owned(),
+    mr.state.clone(),
+    mr.source_branch.clone(),
+    mr.target_branch.clone(),
+  ]
+}
+
+pub fn mrs(mrs: &[MergeRequest], fmt: OutputFormat) -> GLApiResult<()> {
+  let mut records = Records::new(&MR_COLUMNS);
+  for mr in mrs {
+    records.push(mr_row(mr));
+  }
+
+  if fmt.is_table() {
+    println!("Merge requests ({}):", mrs.len());
+  }
+  output::print(fmt, mrs, &records)
+}
+
+#[derive(Serialize)]
+struct ProjectMrs<'a> {
+  project: &'a str,
+  merge_requests: &'a [MergeRequest],
+}
+
+/// Prints merge requests of several projects: table per project,
+/// or a single list with project column for machine-readable formats
+pub fn mrs_of_projects(
+  projects_mrs: &[(String, Vec<MergeRequest>)],
+  fmt: OutputFormat,
+) -> GLApiResult<()> {
+  if fmt.is_table() {
+    for (project, project_mrs) in projects_mrs {
+      println!("{}", project);
+      mrs(project_mrs, fmt)?;
+    }
+    return Ok(());
+  }
+
+  let mut columns = vec![("PROJECT", "project")];
+  columns.extend_from_slice(&MR_COLUMNS);
+  let mut records = Records::new(&columns);
+  let mut data = Vec::with_capacity(projects_mrs.len());
+  for (project, project_mrs) in projects_mrs {
+    for mr in project_mrs {
+      let mut row = vec![project.clone()];
+      row.append(&mut mr_row(mr));
+      records.push(row);
+    }
+    data.push(ProjectMrs {
+      project,
+      merge_requests: project_mrs,
+    });
+  }
+  output::print(fmt, &data, &records)
 }
 
-pub fn mrs(mrs: &[MergeRequest]) {
+/// Prints single merge request in machine-readable format,
+/// e.g. after it was created or updated
+pub fn single_mr(mr: &MergeRequest, fmt: OutputFormat) -> GLApiResult<()> {
+  let mut records = Records::new(&[
+    ("ID", "id"),
+    ("IID", "iid"),
+    ("TITLE", "title"),
+    ("STATE", "state"),
+    ("MERGE_STATUS", "merge_status"),
+    ("SOURCE_BRANCH", "source_branch"),
+    ("TARGET_BRANCH", "target_branch"),
+    ("WEB_URL", "web_url"),
+  ]);
+  records.push(vec![
+    mr.id.to_string(),
+    mr.iid.to_string(),
+    mr.title.clone(),
+    mr.state.clone(),
+    mr.merge_status.clone(),
+    mr.source_branch.clone(),
+    mr.target_branch.clone(),
+    mr.web_url.clone(),
+  ]);
+  output::print(fmt, mr, &records)
+}
+
+#[derive(Serialize)]
+struct MRDetails<'a> {
+  #[serde(flatten)]
+  merge_request: &'a MergeRequest,
+  approvals: Option<&'a MRApprovals>,
+}
+
+pub fn mr_details(
+  mr: &MergeRequest,
+  approvals: Option<&MRApprovals>,
+  fmt: OutputFormat,
+) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    let details = MRDetails {
+      merge_request: mr,
+      approvals,
+    };
+    return match fmt {
+      OutputFormat::Json | OutputFormat::Yaml => output::print(fmt, &details, &Records::new(&[])),
+      _ => single_mr(mr, fmt),
+    };
+  }
+
   let mut table = Table::new();
+  table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+
+  let usernames = |users: &mut dyn Iterator<Item = &User>| {
+    users
+      .map(|u| u.username.as_str())
+      .collect::<Vec<&str>>()
+      .join(", ")
+  };
+
+  let mut assignees = usernames(&mut mr.assignees.iter());
+  if assignees.is_empty() {
+    if let Some(u) = &mr.assignee {
+      assignees = u.username.clone();
+    }
+  }
+  let milestone = mr.milestone.as_ref().map(|m| m.title.as_str());
+  let pipeline = mr.pipeline.as_ref().map(|p| p.status.as_str());
 
+  table.add_row(row!["IID:", mr.iid]);
+  table.add_row(row!["Title:", mr.title]);
+  table.add_row(row!["State:", mr.state]);
+  table.add_row(row!["Author:", mr.author.username]);
+  table.add_row(row!["Assignees:", assignees]);
   table.add_row(row![
-    "ID",
-    "AUTHOR",
-    "ASSIGNEE",
-    "STATE",
-    "SOURCE_BRANCH",
-    "TARGET_BRANCH"
+    "Branches:",
+    format!("{} -> {}", mr.source_branch, mr.target_branch)
   ]);
-  for mr in mrs {
-    let assignee = if let Some(u) = &mr.assignee {
-      &u.username
-    } else {
-      ""
-    };
+  table.add_row(row!["Labels:", mr.labels.join(", ")]);
+  table.add_row(row!["Milestone:", milestone.unwrap_or_default()]);
+  table.add_row(row!["Merge status:", mr.merge_status]);
+  table.add_row(row!["Pipeline:", pipeline.unwrap_or("none")]);
+
+  let approvals_str = if let Some(a) = approvals {
+    let mut s = usernames(&mut a.approved_by.iter().map(|ap| &ap.user));
+    if s.is_empty() {
+      s.push_str("none");
+    }
+    if let (Some(req), Some(left)) = (a.approvals_required, a.approvals_left) {
+      s = format!("{} ({} of {} left)", s, left, req);
+    }
+    s
+  } else {
+    "unavailable".to_owned()
+  };
+  table.add_row(row!["Approved by:", approvals_str]);
+  table.add_row(row!["Created at:", mr.created_at]);
+  table.add_row(row!["URL:", mr.web_url]);
+
+  println!("Merge request !{}:", mr.iid);
+  table.printstd();
+
+  let desc = mr.description.as_deref().unwrap_or_default();
+  if !desc.is_empty() {
+    println!("\n{}", desc);
+  }
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct ConfigPath<'a> {
+  config_path: &'a str,
+}
 
-    table.add_row(row![
-      mr.id,
-      mr.author.username,
-      assignee,
-      mr.state,
-      mr.source_branch,
-      mr.target_branch
+fn config_path(config_path: &str, fmt: OutputFormat) -> GLApiResult<()> {
+  let mut records = Records::new(&[("CONFIG_PATH", "config_path")]);
+  records.push(vec![config_path.to_owned()]);
+  output::print(fmt, &ConfigPath { config_path }, &records)
+}
+
+#[derive(Serialize)]
+struct SavedToken<'a> {
+  host: &'a str,
+  token_store: String,
+  config_path: &'a str,
+}
+
+pub fn save_token(
+  host: &str,
+  store: TokenStore,
+  cfg_path: &str,
+  fmt: OutputFormat,
+) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    let mut records = Records::new(&[
+      ("HOST", "host"),
+      ("TOKEN_STORE", "token_store"),
+      ("CONFIG_PATH", "config_path"),
     ]);
+    records.push(vec![
+      host.to_owned(),
+      store.to_string(),
+      cfg_path.to_owned(),
+    ]);
+    let data = SavedToken {
+      host,
+      token_store: store.to_string(),
+      config_path: cfg_path,
+    };
+    return output::print(fmt, &data, &records);
   }
-  println!("Merge requests ({}):", mrs.len());
-  table.printstd();
+  match store {
+    TokenStore::Plain => println!(
+      "GitLab private token for `{}` saved to `{}`",
+      host, cfg_path
+    ),
+    TokenStore::Keyring => println!(
+      "GitLab private token for `{}` saved to OS secret service",
+      host
+    ),
+    TokenStore::File => println!(
+      "GitLab private token for `{}` saved to encrypted file",
+      host
+    ),
+  }
+  Ok(())
 }
 
-pub fn save_token(config_path: &str) {
-  println!("GitLab private token saved to `{}`:", config_path);
+#[derive(Serialize)]
+struct PrivateToken<'a> {
+  host: &'a str,
+  private_token: Option<&'a str>,
+  token_store: Option<String>,
 }
 
-pub fn show_token(token: Option<&String>) {
-  let mut msg = String::from("Private token is ");
-  if let Some(t) = token {
+pub fn show_token(
+  host: &str,
+  private_token: Option<&str>,
+  store: Option<TokenStore>,
+  fmt: OutputFormat,
+) -> GLApiResult<()> {
+  let store_str = store.map(|s| s.to_string());
+  if !fmt.is_table() {
+    let mut records = Records::new(&[
+      ("HOST", "host"),
+      ("PRIVATE_TOKEN", "private_token"),
+      ("TOKEN_STORE", "token_store"),
+    ]);
+    records.push(vec![
+      host.to_owned(),
+      private_token.unwrap_or_default().to_owned(),
+      store_str.clone().unwrap_or_default(),
+    ]);
+    let data = PrivateToken {
+      host,
+      private_token,
+      token_store: store_str,
+    };
+    return output::print(fmt, &data, &records);
+  }
+
+  let mut msg = format!("Private token for `{}` is ", host);
+  if let Some(t) = private_token {
     msg.push_str(&format!("`{}`", t));
+    if let Some(s) = store_str {
+      msg.push_str(&format!(" ({})", s));
+    }
   } else {
     msg.push_str("not present");
   }
   println!("{}", msg);
+  Ok(())
 }
 
-pub fn forget_token(glob_cfg_path: &str) {
+pub fn forget_token(glob_cfg_path: &str, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    return config_path(glob_cfg_path, fmt);
+  }
   println!(
     "Global config file with private token was deleted ({})",
     glob_cfg_path
   );
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct LoggedIn<'a> {
+  host: &'a str,
+  store: String,
+  config: &'a str,
+}
+
+pub fn logged_in(
+  host: &str,
+  store: TokenStore,
+  config: &str,
+  fmt: OutputFormat,
+) -> GLApiResult<()> {
+  let data = LoggedIn {
+    host,
+    store: store.to_string(),
+    config,
+  };
+  if !fmt.is_table() {
+    let mut records = Records::new(&[("HOST", "host"), ("STORE", "store"), ("CONFIG", "config")]);
+    records.push(vec![host.to_owned(), data.store.clone(), config.to_owned()]);
+    return output::print(fmt, &data, &records);
+  }
+  println!(
+    "Logged in to `{}`, tokens are saved to {} store ({})",
+    host, data.store, config
+  );
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct LoggedOut<'a> {
+  host: &'a str,
+  revoked: bool,
+}
+
+pub fn logged_out(host: &str, revoked: bool, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    let mut records = Records::new(&[("HOST", "host"), ("REVOKED", "revoked")]);
+    records.push(vec![host.to_owned(), revoked.to_string()]);
+    return output::print(fmt, &LoggedOut { host, revoked }, &records);
+  }
+  if revoked {
+    println!(
+      "Tokens of `{}` are revoked and removed from global config",
+      host
+    );
+  } else {
+    println!("There are no valid tokens of `{}` to revoke", host);
+  }
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct WhoAmI<'a> {
+  host: &'a str,
+  user: &'a User,
+  token: Option<&'a PersonalAccessToken>,
+}
+
+pub fn whoami(
+  host: &str,
+  user: &User,
+  token: Option<&PersonalAccessToken>,
+  fmt: OutputFormat,
+) -> GLApiResult<()> {
+  let scopes = token.map(|t| t.scopes.join(", ")).unwrap_or_default();
+  let expires_at = token.map_or("", |t| t.expires_at.as_deref().unwrap_or("never"));
+  if !fmt.is_table() {
+    let mut records = Records::new(&[
+      ("HOST", "host"),
+      ("ID", "id"),
+      ("USERNAME", "username"),
+      ("NAME", "name"),
+      ("TOKEN", "token"),
+      ("SCOPES", "scopes"),
+      ("EXPIRES_AT", "expires_at"),
+    ]);
+    records.push(vec![
+      host.to_owned(),
+      user.id.to_string(),
+      user.username.clone(),
+      user.name.clone(),
+      token.map(|t| t.name.clone()).unwrap_or_default(),
+      scopes,
+      expires_at.to_owned(),
+    ]);
+    return output::print(fmt, &WhoAmI { host, user, token }, &records);
+  }
+
+  let mut table = Table::new();
+  table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+  table.add_row(row!["Username:", user.username]);
+  table.add_row(row!["ID:", user.id]);
+  table.add_row(row!["Name:", user.name]);
+  match token {
+    Some(t) => {
+      table.add_row(row!["Token:", t.name]);
+      table.add_row(row!["Scopes:", scopes]);
+      table.add_row(row!["Expires at:", expires_at]);
+    }
+    None => {
+      table.add_row(row!["Token:", "info is unavailable"]);
+    }
+  }
+  println!("Logged in to `{}`:", host);
+  table.printstd();
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct ClearedCache<'a> {
+  dir: Option<&'a str>,
+  removed: usize,
+}
+
+pub fn cache_cleared(dir: Option<&str>, removed: usize, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    let mut records = Records::new(&[("DIR", "dir"), ("REMOVED", "removed")]);
+    records.push(vec![
+      dir.unwrap_or_default().to_owned(),
+      removed.to_string(),
+    ]);
+    return output::print(fmt, &ClearedCache { dir, removed }, &records);
+  }
+  match dir {
+    Some(d) => println!("{} cached responses were removed ({})", removed, d),
+    None => println!("Cache directory is unknown, nothing to remove"),
+  }
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct RemovedHost<'a> {
+  host: &'a str,
+  removed: bool,
+}
+
+pub fn forget_host(host: &str, removed: bool, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    let mut records = Records::new(&[("HOST", "host"), ("REMOVED", "removed")]);
+    records.push(vec![host.to_owned(), removed.to_string()]);
+    return output::print(fmt, &RemovedHost { host, removed }, &records);
+  }
+  if removed {
+    println!("Settings of `{}` were removed from global config", host);
+  } else {
+    println!("There are no settings of `{}` in global config", host);
+  }
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct HostInfo<'a> {
+  host: &'a str,
+  has_token: bool,
+  token_store: Option<String>,
+  api_version: Option<&'a str>,
+  ca_cert: Option<&'a str>,
+  accept_invalid_certs: bool,
+}
+
+pub fn hosts(hosts: &BTreeMap<String, HostData>, fmt: OutputFormat) -> GLApiResult<()> {
+  let infos: Vec<HostInfo> = hosts
+    .iter()
+    .map(|(host, data)| HostInfo {
+      host,
+      has_token: data.token_store().is_some(),
+      token_store: data.token_store().map(|s| s.to_string()),
+      api_version: data.api_version.as_deref(),
+      ca_cert: data.ca_cert.as_deref(),
+      accept_invalid_certs: data.accept_invalid_certs == Some(true),
+    })
+    .collect();
+
+  let mut records = Records::new(&[
+    ("HOST", "host"),
+    ("TOKEN", "token_store"),
+    ("API", "api_version"),
+    ("CA_CERT", "ca_cert"),
+    ("INSECURE", "accept_invalid_certs"),
+  ]);
+  for i in &infos {
+    records.push(vec![
+      i.host.to_owned(),
+      i.token_store.clone().unwrap_or_default(),
+      i.api_version.unwrap_or_default().to_owned(),
+      i.ca_cert.unwrap_or_default().to_owned(),
+      i.accept_invalid_certs.to_string(),
+    ]);
+  }
+
+  if fmt.is_table() {
+    println!("Hosts ({}):", infos.len());
+  }
+  output::print(fmt, &infos, &records)
 }
@@ -1,86 +1,311 @@
 #[macro_use]
-extern crate failure;
-#[macro_use]
 extern crate serde;
 #[macro_use]
 extern crate clap;
 #[macro_use]
 extern crate prettytable;
 
-mod api;
 mod args;
-mod configs;
+mod commit_summary;
 mod create_mr;
+mod editor;
 mod helpers;
+mod logger;
+mod login;
 mod ls;
+mod merge_mr;
+mod mr_template;
+mod output;
+mod push;
+mod update_mr;
 
 use args::{get_matches, parse_args, ArgName, Args};
 use clap::ArgMatches;
-use configs::{CfgVariant, Configs};
+use mergereq::configs::{self, CfgVariant, Configs, EnvVars, ANY_HOST};
+use mergereq::settings::{self, Overrides};
+use mergereq::{api, secrets};
+use output::OutputFormat;
+use std::str::FromStr;
 
-use api::{GLApi, ReqParams};
+use api::{
+  Cassette, GLApi, GLApiError, GLApiResult, OAuthClient, OAuthSession, PersonalAccessToken,
+  RecordingTransport, ReplayTransport, ReqParams, ReqwestTransport, ResponseCache, DEFAULT_SCOPES,
+};
 
 fn main() {
-  if let Err(err) = run() {
+  let matches = get_matches();
+  let verbosity = if matches.is_present(ArgName::Debug) {
+    3
+  } else {
+    matches.occurrences_of(ArgName::Verbose)
+  };
+  let log_file = matches.value_of(ArgName::LogFile);
+  if let Err(err) = logger::init(logger::level_for_verbosity(verbosity), log_file) {
+    eprintln!("[ERROR] Cannot open log file: {}", err);
+    std::process::exit(1);
+  }
+
+  if let Err(err) = run(&matches) {
     eprintln!("[ERROR] {}", err);
+    if verbosity > 0 {
+      if let Some(body) = err.downcast_ref::<GLApiError>().and_then(|e| e.body()) {
+        eprintln!("[RESPONSE] {}", body);
+      }
+    }
     std::process::exit(1);
   }
 }
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
-  let matches = get_matches();
-
+fn run(matches: &ArgMatches) -> GLApiResult<()> {
   let mut configs = Configs::read(
     matches.value_of(ArgName::GlobalConfig),
     matches.value_of(ArgName::LocalConfig),
   )?;
 
-  let arg = parse_args(&matches);
+  let arg = parse_args(matches);
+
+  let fmt = matches
+    .value_of(ArgName::Output)
+    .and_then(|f| OutputFormat::from_str(f).ok())
+    .unwrap_or_default();
+
+  let env = EnvVars::read();
+
+  let overrides = collect_overrides(matches);
+  let req_params = settings::resolve_req_params(&configs, &env, &overrides);
+
+  let repo_url_host = req_params.repo_url.as_deref().map(configs::host_key);
 
-  let req_params = collect_req_params(&matches, &configs);
+  let cache = settings::resolve_cache(&env);
+  let gl = init_api(matches, &configs, req_params, cache.clone())?;
 
-  let gl = GLApi::init(req_params);
+  let warning_days =
+    settings::token_expiry_warning_days(&configs, gl.req_params.repo_url.as_deref());
+  let checks_token = match arg {
+    Args::LsUsers { .. }
+    | Args::LsProjects { .. }
+    | Args::LsBranches { .. }
+    | Args::LsMr { .. }
+    | Args::ShowMr { .. }
+    | Args::CreateMR(_)
+    | Args::UpdateMR { .. }
+    | Args::MergeMR { .. } => !matches.is_present(ArgName::Replay),
+    _ => false,
+  };
+  if checks_token && warning_days > 0 {
+    check_token(&gl, warning_days)?;
+  }
 
   match arg {
-    Args::LsUsers(q) => {
-      let users = gl.get_users(&q)?;
-      ls::users(&users);
+    Args::LsUsers { query, pagination } => {
+      let users = gl.get_users(&query, &pagination)?;
+      ls::users(&users, fmt)?;
+    }
+    Args::LsProjects { query, pagination } => {
+      let projects = gl.get_projects(&query, &pagination)?;
+      ls::projects(&projects, fmt)?;
     }
-    Args::LsProjects(q) => {
-      let projects = gl.get_projects(&q)?;
-      ls::projects(&projects);
+    Args::LsBranches {
+      project,
+      query,
+      pagination,
+    } => {
+      let branches = gl.get_project_branches(project, &query, &pagination)?;
+      ls::branches(&branches, fmt)?;
     }
-    Args::LsBranches { project, query } => {
-      let branches = gl.get_project_branches(project, &query)?;
-      ls::branches(&branches);
+    Args::LsMr {
+      projects: Some(projects),
+      query,
+      pagination,
+      ..
+    } => {
+      let projects_mrs = gl.get_merge_requests_of_projects(&projects, &query, &pagination)?;
+      ls::mrs_of_projects(&projects_mrs, fmt)?;
     }
-    Args::LsMr { project, query } => {
-      let mrs = gl.get_project_merge_requests(project, &query)?;
-      ls::mrs(&mrs);
+    Args::LsMr {
+      project,
+      projects: None,
+      query,
+      pagination,
+    } => {
+      let mrs = gl.get_project_merge_requests(project, &query, &pagination)?;
+      ls::mrs(&mrs, fmt)?;
+    }
+    Args::ShowMr { project, iid } => {
+      let mr = gl.get_merge_request(project, iid)?;
+      let approvals = gl.get_merge_request_approvals(project, iid).ok();
+      ls::mr_details(&mr, approvals.as_ref(), fmt)?;
     }
     Args::CreateMR(args_matches) => {
       let project = gl.req_params.get_default_project_checked()?;
-      let create_mr_data = create_mr::fill_mr_create_data(&gl, project, &args_matches);
-      create_mr::confirm_mr(&create_mr_data, &args_matches);
-      let mr = gl.create_merge_request(project, &create_mr_data)?;
-      create_mr::log_new_mr(&mr);
+      let mut create_mr_data = create_mr::fill_mr_create_data(&gl, project, args_matches);
+      let existing = create_mr::find_open_mr(&gl, project, &create_mr_data)?;
+      if let Some(mr) = &existing {
+        create_mr::log_existing_mr(mr);
+      }
+      let remote = settings::remote_name(&configs, &overrides);
+      let description;
+      if create_mr_data.description.is_none() {
+        let source = create_mr::description_source(args_matches, configs.local.as_ref())?;
+        if let Some(d) =
+          create_mr::generate_description(&gl, project, &create_mr_data, source, remote)?
+        {
+          description = d;
+          create_mr_data.description = Some(&description);
+        }
+      }
+      let edited;
+      if args_matches.is_present(ArgName::Edit) {
+        edited = create_mr::edit_mr(&create_mr_data, remote)?;
+        create_mr_data.title = edited.title.clone();
+        create_mr_data.description = edited.description.as_deref();
+      }
+      let mut push_settings = create_mr::PushSettings::from_args(args_matches);
+      if let Some(existing) = existing {
+        let update_mr_data =
+          create_mr::update_existing_data(&existing, &create_mr_data, args_matches);
+        push_settings.create_on_push = false;
+        if update_mr_data.is_empty() {
+          // nothing to change, but new local commits still have to be pushed
+          create_mr::push_source_branch(&gl, project, &create_mr_data, remote, &push_settings)?;
+          create_mr::log_unchanged_mr(&existing, fmt)?;
+        } else {
+          update_mr::confirm_update(&existing, &update_mr_data);
+          create_mr::push_source_branch(&gl, project, &create_mr_data, remote, &push_settings)?;
+          let mr = gl.update_merge_request(Some(project), existing.iid, &update_mr_data)?;
+          update_mr::log_updated_mr(&mr, fmt)?;
+        }
+      } else {
+        create_mr::confirm_mr(&create_mr_data, args_matches);
+        let pushed =
+          create_mr::push_source_branch(&gl, project, &create_mr_data, remote, &push_settings)?;
+        let mr = match pushed {
+          Some(mr) => mr,
+          None => gl.create_merge_request(project, &create_mr_data)?,
+        };
+        create_mr::log_new_mr(&mr, fmt)?;
+      }
     }
 
-    Args::CfgSaveToken(token) => {
-      configs.save_new_token(token)?;
-      ls::save_token(configs.get_file_path(CfgVariant::Global));
+    Args::UpdateMR {
+      project,
+      iid,
+      args_matches,
+    } => {
+      let mr = gl.get_merge_request(project, iid)?;
+      let update_mr_data = update_mr::fill_mr_update_data(&gl, args_matches);
+      update_mr::confirm_update(&mr, &update_mr_data);
+      let mr = gl.update_merge_request(project, iid, &update_mr_data)?;
+      update_mr::log_updated_mr(&mr, fmt)?;
+    }
+    Args::MergeMR { project, iid, body } => {
+      let mr = gl.get_merge_request(project, iid)?;
+      merge_mr::confirm_merge(&mr, &body);
+      let mr = gl.merge_merge_request(project, iid, &body)?;
+      merge_mr::log_merged_mr(&mr, fmt)?;
+    }
+
+    Args::CfgSaveToken { token, host, store } => {
+      let host = host
+        .map(configs::host_key)
+        .or(repo_url_host)
+        .unwrap_or_else(|| ANY_HOST.to_owned());
+      let store = configs.save_new_token(&host, token, store)?;
+      ls::save_token(&host, store, configs.get_file_path(CfgVariant::Global), fmt)?;
+    }
+    Args::CfgShowToken { host } => {
+      let explicit_host = host.map(configs::host_key);
+      let host = explicit_host
+        .clone()
+        .or(repo_url_host)
+        .unwrap_or_else(|| ANY_HOST.to_owned());
+      let host_data = configs.global.as_ref().and_then(|g| {
+        let found = g.hosts.get(&host);
+        if explicit_host.is_some() {
+          found
+        } else {
+          found.or_else(|| g.hosts.get(ANY_HOST))
+        }
+      });
+      let tok = host_data.and_then(|h| configs.host_token(h));
+      let store = host_data.and_then(|h| h.token_store());
+      ls::show_token(&host, tok, store, fmt)?;
+    }
+    Args::CfgForgetToken { host } => {
+      if let Some(h) = host {
+        let host = configs::host_key(h);
+        let removed = configs.remove_host(&host)?;
+        ls::forget_host(&host, removed, fmt)?;
+      } else {
+        configs.remove_global_cfg()?;
+        let glob_cfg_path = configs.get_file_path(CfgVariant::Global);
+        ls::forget_token(glob_cfg_path, fmt)?;
+      }
     }
-    Args::CfgShowToken => {
-      let mut tok = None;
+    Args::CfgLsHosts => {
       if let Some(glob) = &configs.global {
-        tok = Some(&glob.private_token);
+        ls::hosts(&glob.hosts, fmt)?;
+      } else {
+        ls::hosts(&Default::default(), fmt)?;
       }
-      ls::show_token(tok);
     }
-    Args::CfgForgetToken => {
-      configs.remove_global_cfg()?;
-      let glob_cfg_path = configs.get_file_path(CfgVariant::Global);
-      ls::forget_token(glob_cfg_path);
+
+    Args::Login {
+      client_id,
+      device,
+      port,
+      scopes,
+      store,
+    } => {
+      let client = oauth_client(&configs, &gl.req_params, client_id)?;
+      let scopes = scopes.unwrap_or(DEFAULT_SCOPES);
+      let token = if device {
+        login::with_device(&client, scopes)?
+      } else {
+        login::with_browser(&client, port.unwrap_or(login::DEFAULT_PORT), scopes)?
+      };
+      let host = configs::host_key(gl.req_params.get_repo_url_checked()?);
+      let store = configs.save_oauth_token(&host, client.client_id(), &token, store)?;
+      ls::logged_in(&host, store, configs.get_file_path(CfgVariant::Global), fmt)?;
+    }
+    Args::Logout => {
+      let repo_url = gl.req_params.get_repo_url_checked()?;
+      let host = configs
+        .global
+        .as_ref()
+        .and_then(|g| g.host_entry_for_url(repo_url))
+        .map_or_else(|| configs::host_key(repo_url), |(key, _)| key.to_owned());
+      let client = oauth_client(&configs, &gl.req_params, None);
+      let token = configs.remove_oauth_token(&host)?;
+      let revoked = match (token, client) {
+        (Some(t), Ok(c)) => match c.revoke(&t.access_token) {
+          Ok(()) => true,
+          Err(e) => {
+            log::warn!("Cannot revoke tokens: {}", e);
+            false
+          }
+        },
+        _ => false,
+      };
+      ls::logged_out(&host, revoked, fmt)?;
+    }
+
+    Args::Whoami => {
+      let repo_url = gl.req_params.get_repo_url_checked()?;
+      let user = gl.get_current_user()?;
+      let token = check_token(&gl, warning_days)?;
+      ls::whoami(&configs::host_key(repo_url), &user, token.as_ref(), fmt)?;
+    }
+
+    Args::CacheClear => {
+      let removed = match &cache {
+        Some(c) => c.clear()?,
+        None => 0,
+      };
+      let dir = cache
+        .as_ref()
+        .map(|c| c.dir().to_string_lossy().into_owned());
+      ls::cache_cleared(dir.as_deref(), removed, fmt)?;
     }
 
     Args::Unknown => {
@@ -91,25 +316,153 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
   Ok(())
 }
 
-fn collect_req_params<'a>(matches: &'a ArgMatches, cfg: &'a Configs) -> ReqParams<'a> {
-  let global_ref = cfg.global.as_ref();
-  let local_ref = cfg.local.as_ref();
+/// Info of the private token. Warns when it expires within `warning_days` or lacks `api` scope.
+/// `None` for tokens of `login` and CI job tokens, or if GitLab does not provide the info,
+/// e.g. before version 16.0. Errors of GitLab API are only logged, as the command may still work,
+/// while transport errors (e.g. GitLab is unreachable) fail the command
+fn check_token(gl: &GLApi, warning_days: u32) -> GLApiResult<Option<PersonalAccessToken>> {
+  if gl.req_params.oauth || gl.req_params.job_token || gl.req_params.private_token.is_none() {
+    return Ok(None);
+  }
+  let token = match gl.get_current_token() {
+    Ok(t) => t,
+    // e.g. GitLab older than 16.0 has no such endpoint, the command itself may still work
+    Err(e) if e.downcast_ref::<GLApiError>().is_some() => {
+      log::info!("Cannot get info of private token: {}", e);
+      return Ok(None);
+    }
+    // GitLab is unreachable, so is the command
+    Err(e) => return Err(e),
+  };
+  for w in token.warnings(warning_days) {
+    log::warn!("{}", w);
+  }
+  Ok(Some(token))
+}
+
+/// OAuth application given with `--client-id` or saved for host of repo_url by `login`
+fn oauth_client(
+  configs: &Configs,
+  req_params: &ReqParams,
+  client_id: Option<&str>,
+) -> GLApiResult<OAuthClient> {
+  let repo_url = req_params.get_repo_url_checked()?;
+  let saved_id = configs
+    .global
+    .as_ref()
+    .and_then(|g| g.host_for_url(repo_url))
+    .and_then(|h| h.oauth_client_id.as_deref());
+  let client_id = client_id.or(saved_id).ok_or(GLApiError::NoOAuthClientId)?;
+  let transport = ReqwestTransport::new(req_params.connection.clone());
+  Ok(OAuthClient::new(repo_url, client_id, transport))
+}
+
+/// Session with tokens of `login`. Refreshed tokens are saved to global config
+/// right away, as GitLab revokes the old refresh token
+fn oauth_session(configs: &Configs, req_params: &ReqParams) -> GLApiResult<OAuthSession> {
+  let repo_url = req_params.get_repo_url_checked()?;
+  let (host, host_data) = configs
+    .global
+    .as_ref()
+    .and_then(|g| g.host_entry_for_url(repo_url))
+    .ok_or(GLApiError::NoPrivateToken)?;
+  let token = configs
+    .host_oauth_token(host_data)
+    .cloned()
+    .ok_or(GLApiError::NoPrivateToken)?;
+  let store = host_data
+    .oauth_token_store()
+    .unwrap_or(secrets::TokenStore::Plain);
+  let client = oauth_client(configs, req_params, None)?;
 
-  let token = matches
-    .value_of(ArgName::PrivateToken)
-    .or_else(|| global_ref.map(|glob| glob.private_token.as_str()));
+  let host = host.to_owned();
+  let client_id = client.client_id().to_owned();
+  let global_path = configs.get_file_path(CfgVariant::Global).to_owned();
+  let session = OAuthSession::new(client, token).on_refresh(move |t| {
+    let mut configs = Configs::read(Some(&global_path), None)?;
+    configs.save_oauth_token(&host, &client_id, t, store)?;
+    Ok(())
+  });
+  Ok(session)
+}
+
+/// Client of GitLab. With `--record` it writes API traffic to cassette,
+/// with `--replay` it serves responses from cassette. Otherwise responses of projects
+/// and users are cached, unless `--no-cache` is passed.
+/// Tokens of `login` are refreshed when they expire
+fn init_api(
+  matches: &ArgMatches,
+  configs: &Configs,
+  mut req_params: ReqParams,
+  cache: Option<ResponseCache>,
+) -> GLApiResult<GLApi> {
+  let oauth = if req_params.oauth && !matches.is_present(ArgName::Replay) {
+    Some(oauth_session(configs, &req_params)?)
+  } else {
+    None
+  };
+
+  if let Some(path) = matches.value_of(ArgName::Record) {
+    let transport = ReqwestTransport::new(req_params.connection.clone());
+    let recording = RecordingTransport::new(transport, path);
+    let gl = GLApi::with_transport(req_params, recording);
+    return Ok(match oauth {
+      Some(session) => gl.oauth(session),
+      None => gl,
+    });
+  }
 
-  let project = matches
-    .value_of(ArgName::Project)
-    .or_else(|| local_ref.and_then(|loc| loc.default_project.as_ref().map(String::as_str)));
+  if let Some(path) = matches.value_of(ArgName::Replay) {
+    let cassette = Cassette::load(path)?;
+    if req_params.repo_url.is_none() {
+      req_params.repo_url = cassette.base_url();
+    }
+    // tokens are redacted in cassette, any value is fine
+    if req_params.private_token.is_none() {
+      req_params.private_token = Some("replay".to_owned());
+    }
+    return Ok(GLApi::with_transport(
+      req_params,
+      ReplayTransport::new(cassette),
+    ));
+  }
 
-  let repo_url = matches
-    .value_of(ArgName::RepoUrl)
-    .or_else(|| local_ref.map(|loc| loc.repo_url.as_str()));
+  let mut gl = GLApi::init(req_params);
+  if let Some(session) = oauth {
+    gl = gl.oauth(session);
+  }
+  match cache {
+    Some(c) if !matches.is_present(ArgName::NoCache) => Ok(gl.cache(c)),
+    _ => Ok(gl),
+  }
+}
+
+/// Parses numeric option, exiting with clap error if it is invalid
+fn num_arg<T: FromStr>(matches: &ArgMatches, arg: ArgName) -> Option<T> {
+  let name: &str = arg.into();
+  if !matches.is_present(name) {
+    return None;
+  }
+  Some(value_t!(matches, name, T).unwrap_or_else(|e| e.exit()))
+}
 
-  ReqParams {
-    private_token: token,
-    repo_url,
-    default_project: project,
+fn collect_overrides(matches: &ArgMatches) -> Overrides {
+  let string_arg = |arg: ArgName| matches.value_of(arg).map(str::to_owned);
+  Overrides {
+    private_token: string_arg(ArgName::PrivateToken),
+    repo_url: string_arg(ArgName::RepoUrl),
+    project: string_arg(ArgName::Project),
+    remote: string_arg(ArgName::Remote),
+    retries: num_arg(matches, ArgName::Retries),
+    request_budget: num_arg(matches, ArgName::RequestBudget),
+    retry_non_idempotent: matches.is_present(ArgName::RetryNonIdempotent),
+    connect_timeout: num_arg(matches, ArgName::ConnectTimeout),
+    timeout: num_arg(matches, ArgName::Timeout),
+    proxy: string_arg(ArgName::Proxy),
+    no_proxy: string_arg(ArgName::NoProxy),
+    ca_cert: string_arg(ArgName::CaCert),
+    client_identity: string_arg(ArgName::ClientIdentity),
+    insecure: matches.is_present(ArgName::Insecure),
+    concurrency: num_arg(matches, ArgName::Concurrency),
   }
 }
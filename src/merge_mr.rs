@@ -0,0 +1,53 @@
+use super::{helpers, ls};
+use crate::api::{GLApiResult, MergeMRBody, MergeRequest};
+use crate::output::OutputFormat;
+
+fn yes_no(val: Option<bool>) -> &'static str {
+  if val.unwrap_or(false) {
+    "yes"
+  } else {
+    "no"
+  }
+}
+
+pub fn confirm_merge(mr: &MergeRequest, body: &MergeMRBody) {
+  eprintln!("You merging merge request !{}:", mr.iid);
+  eprintln!("  Title:         — {}", helpers::get_one_line(&mr.title));
+  eprintln!("  Source branch: — {}", mr.source_branch);
+  eprintln!("  Target branch: — {}", mr.target_branch);
+  eprintln!("  Merge status:  — {}", mr.merge_status);
+  eprintln!("  Squash:        — {}", yes_no(body.squash));
+  eprintln!(
+    "  Remove source: — {}",
+    yes_no(body.should_remove_source_branch)
+  );
+  if body.merge_when_pipeline_succeeds.unwrap_or(false) {
+    eprintln!("  Will be merged when the pipeline succeeds");
+  }
+
+  helpers::confirm_or_exit();
+}
+
+pub fn log_merged_mr(mr: &MergeRequest, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    return ls::single_mr(mr, fmt);
+  }
+  if mr.state == "merged" {
+    println!(
+      "\nMerge request !{} is merged. You can see it here:",
+      mr.iid
+    );
+  } else if mr.merge_when_pipeline_succeeds {
+    println!(
+      "\nMerge request !{} will be merged when the pipeline succeeds. You can see it here:",
+      mr.iid
+    );
+  } else {
+    println!(
+      "\nMerge request !{} is {}. You can see it here:",
+      mr.iid, mr.state
+    );
+  }
+  println!("{}", mr.web_url);
+  Ok(())
+}
@@ -0,0 +1,102 @@
+use mergereq::api::{GLApi, GLApiError, GLApiResult};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Directory of merge request templates in repository, the same as GitLab uses
+pub const TEMPLATES_DIR: &str = ".gitlab/merge_request_templates";
+
+/// Values of placeholders of template
+pub struct TemplateVars<'a> {
+  pub source_branch: &'a str,
+  pub target_branch: &'a str,
+  pub title: &'a str,
+  /// Subjects of commits of source branch that are not in target branch
+  pub commits: Vec<String>,
+  /// Issue linked by the name of source branch, e.g. `123` of `123-fix-login`
+  pub issue: Option<u32>,
+}
+
+/// Path of template file in repository, e.g. `.gitlab/merge_request_templates/default.md`
+fn template_path(name: &str) -> String {
+  let name = name.strip_suffix(".md").unwrap_or(name);
+  format!("{}/{}.md", TEMPLATES_DIR, name)
+}
+
+/// Reads template from the local repository, or requests it from `git_ref` of the project
+/// if there is no such file locally
+pub fn load(gl: &GLApi, project: &str, name: &str, git_ref: &str) -> GLApiResult<String> {
+  let path = template_path(name);
+  if let Some(local) = repo_root().map(|root| root.join(&path)) {
+    if let Ok(text) = fs::read_to_string(&local) {
+      log::info!("Template `{}` is read from {}", name, local.display());
+      return Ok(text);
+    }
+  }
+
+  match gl.get_file(Some(project), &path, git_ref) {
+    Ok(file) => {
+      log::info!(
+        "Template `{}` is taken from {} of {}",
+        name,
+        git_ref,
+        project
+      );
+      file.text()
+    }
+    Err(err) => match err.downcast_ref::<GLApiError>() {
+      Some(GLApiError::NotFound { .. }) => Err(
+        GLApiError::TemplateNotFound {
+          name: name.to_owned(),
+          path,
+          git_ref: git_ref.to_owned(),
+        }
+        .into(),
+      ),
+      _ => Err(err),
+    },
+  }
+}
+
+/// Replaces placeholders of template:
+/// * `%{source_branch}`, `%{target_branch}`, `%{title}`
+/// * `%{all_commits}` - list of subjects of commits, `%{first_commit}` - subject of the first one
+/// * `%{issue}` - linked issue as `#123`, empty if source branch is not named after issue
+pub fn render(template: &str, vars: &TemplateVars) -> String {
+  let all_commits: Vec<String> = vars.commits.iter().map(|c| format!("- {}", c)).collect();
+  let first_commit = vars.commits.first().map(String::as_str).unwrap_or_default();
+  let issue = vars.issue.map(|i| format!("#{}", i)).unwrap_or_default();
+  template
+    .replace("%{source_branch}", vars.source_branch)
+    .replace("%{target_branch}", vars.target_branch)
+    .replace("%{title}", vars.title)
+    .replace("%{all_commits}", &all_commits.join("\n"))
+    .replace("%{first_commit}", first_commit)
+    .replace("%{issue}", &issue)
+}
+
+/// Issue of branch named like GitLab names branches created from issues,
+/// e.g. `123-fix-login` or `feature/123-fix-login`
+pub fn issue_from_branch(branch: &str) -> Option<u32> {
+  let name = branch.rsplit('/').next()?;
+  let digits: &str = name.split(|c: char| !c.is_ascii_digit()).next()?;
+  let rest = &name[digits.len()..];
+  if rest.is_empty() || rest.starts_with('-') || rest.starts_with('_') {
+    digits.parse().ok()
+  } else {
+    None
+  }
+}
+
+fn repo_root() -> Option<PathBuf> {
+  let output = Command::new("git")
+    .args(["rev-parse", "--show-toplevel"])
+    .output()
+    .ok()?;
+  let root = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+  if output.status.success() && !root.is_empty() {
+    Some(PathBuf::from(root))
+  } else {
+    None
+  }
+}
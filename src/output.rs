@@ -0,0 +1,122 @@
+use prettytable::{Row, Table};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::api::{GLApiError, GLApiResult};
+
+const FORMAT_TABLE: &str = "table";
+const FORMAT_JSON: &str = "json";
+const FORMAT_YAML: &str = "yaml";
+const FORMAT_CSV: &str = "csv";
+const FORMAT_TSV: &str = "tsv";
+
+pub const POSSIBLE_VALUES: [&str; 5] = [
+  FORMAT_TABLE,
+  FORMAT_JSON,
+  FORMAT_YAML,
+  FORMAT_CSV,
+  FORMAT_TSV,
+];
+
+#[derive(Copy, Clone, PartialEq, Default)]
+pub enum OutputFormat {
+  #[default]
+  Table,
+  Json,
+  Yaml,
+  Csv,
+  Tsv,
+}
+
+impl OutputFormat {
+  pub fn is_table(self) -> bool {
+    self == OutputFormat::Table
+  }
+}
+
+impl FromStr for OutputFormat {
+  type Err = GLApiError;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      FORMAT_TABLE => Ok(OutputFormat::Table),
+      FORMAT_JSON => Ok(OutputFormat::Json),
+      FORMAT_YAML => Ok(OutputFormat::Yaml),
+      FORMAT_CSV => Ok(OutputFormat::Csv),
+      FORMAT_TSV => Ok(OutputFormat::Tsv),
+      _ => Err(GLApiError::ParseError(s.to_owned())),
+    }
+  }
+}
+
+impl fmt::Display for OutputFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let s = match self {
+      OutputFormat::Table => FORMAT_TABLE,
+      OutputFormat::Json => FORMAT_JSON,
+      OutputFormat::Yaml => FORMAT_YAML,
+      OutputFormat::Csv => FORMAT_CSV,
+      OutputFormat::Tsv => FORMAT_TSV,
+    };
+    write!(f, "{}", s)
+  }
+}
+
+/// Flat representation of the printed data, used for tables and CSV/TSV.
+/// Each column has a title for the table and a stable field name for CSV/TSV header.
+pub struct Records {
+  columns: Vec<(&'static str, &'static str)>,
+  rows: Vec<Vec<String>>,
+}
+
+impl Records {
+  pub fn new(columns: &[(&'static str, &'static str)]) -> Self {
+    Records {
+      columns: columns.to_vec(),
+      rows: Vec::new(),
+    }
+  }
+
+  pub fn push(&mut self, row: Vec<String>) {
+    self.rows.push(row);
+  }
+
+  pub fn to_table(&self) -> Table {
+    let mut table = Table::new();
+    table.add_row(self.columns.iter().map(|(title, _)| title).collect::<Row>());
+    for r in &self.rows {
+      table.add_row(r.iter().collect::<Row>());
+    }
+    table
+  }
+
+  fn write_delimited(&self, delimiter: u8) -> GLApiResult<()> {
+    let mut wtr = csv::WriterBuilder::new()
+      .delimiter(delimiter)
+      .from_writer(std::io::stdout());
+    wtr.write_record(self.columns.iter().map(|(_, field)| field))?;
+    for r in &self.rows {
+      wtr.write_record(r)?;
+    }
+    wtr.flush()?;
+    Ok(())
+  }
+}
+
+/// Prints data in the given format.
+/// JSON and YAML contain full data, table, CSV and TSV contain only the `records` fields.
+/// Callers with a custom table layout print their own table instead of calling it.
+pub fn print<T: Serialize + ?Sized>(
+  format: OutputFormat,
+  data: &T,
+  records: &Records,
+) -> GLApiResult<()> {
+  match format {
+    OutputFormat::Table => records.to_table().printstd(),
+    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(data)?),
+    OutputFormat::Yaml => println!("{}", serde_yaml::to_string(data)?),
+    OutputFormat::Csv => records.write_delimited(b',')?,
+    OutputFormat::Tsv => records.write_delimited(b'\t')?,
+  }
+  Ok(())
+}
@@ -0,0 +1,99 @@
+use mergereq::api::{GLApi, GLApiError, GLApiResult};
+use std::process::Command;
+
+/// Whether `create mr` pushes the source branch that is missing on the remote or behind local
+#[derive(Copy, Clone, PartialEq)]
+pub enum PushMode {
+  /// Asks before pushing
+  Ask,
+  /// Pushes without asking, `--push`
+  Always,
+  /// Does not check the branch at all, `--no-push`
+  Never,
+}
+
+/// State of the local branch compared to the branch of the project
+pub enum BranchState {
+  /// There is no such local branch, so there is nothing to push
+  NotLocal,
+  /// The project has the same commit, or a newer one based on the local branch
+  UpToDate,
+  /// The project has no such branch
+  Missing,
+  /// The project branch is behind local by the given count of commits
+  Behind(usize),
+  /// The project branch has commits the local branch does not have
+  Diverged,
+}
+
+impl BranchState {
+  pub fn needs_push(&self) -> bool {
+    matches!(self, BranchState::Missing | BranchState::Behind(_))
+  }
+}
+
+/// Compares the local `branch` with the same branch of the project
+pub fn branch_state(gl: &GLApi, project: &str, branch: &str) -> GLApiResult<BranchState> {
+  let local = match rev_parse(&format!("refs/heads/{}", branch)) {
+    Some(sha) => sha,
+    None => return Ok(BranchState::NotLocal),
+  };
+  let remote = match gl.get_project_branch(Some(project), branch) {
+    Ok(b) => b.commit.id,
+    Err(err) => match err.downcast_ref::<GLApiError>() {
+      Some(GLApiError::NotFound { .. }) => return Ok(BranchState::Missing),
+      _ => return Err(err),
+    },
+  };
+
+  if remote == local || is_ancestor(&local, &remote) {
+    Ok(BranchState::UpToDate)
+  } else if is_ancestor(&remote, &local) {
+    let count = git_output(&["rev-list", "--count", &format!("{}..{}", remote, local)])
+      .and_then(|c| c.parse().ok())
+      .unwrap_or_default();
+    Ok(BranchState::Behind(count))
+  } else {
+    Ok(BranchState::Diverged)
+  }
+}
+
+/// Runs `git push -u <remote> <branch>` with GitLab push options, e.g. `merge_request.create`
+pub fn push(remote: &str, branch: &str, options: &[String]) -> GLApiResult<()> {
+  let mut cmd = Command::new("git");
+  cmd.args(["push", "-u"]);
+  for o in options {
+    cmd.args(["-o", o]);
+  }
+  // stdout is kept for output of mergereq, so messages of git go to stderr
+  cmd.args([remote, branch]).stdout(std::io::stderr());
+  log::info!("Running git {:?}", cmd.get_args().collect::<Vec<_>>());
+
+  match cmd.status() {
+    Ok(s) if s.success() => Ok(()),
+    Ok(s) => Err(GLApiError::Git(format!("`git push` exited with {}", s)).into()),
+    Err(e) => Err(GLApiError::Git(format!("cannot run `git push`: {}", e)).into()),
+  }
+}
+
+fn rev_parse(git_ref: &str) -> Option<String> {
+  git_output(&["rev-parse", "--verify", "--quiet", git_ref])
+}
+
+/// Whether `ancestor` is reachable from `descendant`. Commits unknown locally are not
+fn is_ancestor(ancestor: &str, descendant: &str) -> bool {
+  Command::new("git")
+    .args(["merge-base", "--is-ancestor", ancestor, descendant])
+    .output()
+    .is_ok_and(|o| o.status.success())
+}
+
+fn git_output(args: &[&str]) -> Option<String> {
+  let output = Command::new("git").args(args).output().ok()?;
+  let out = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+  if output.status.success() && !out.is_empty() {
+    Some(out)
+  } else {
+    None
+  }
+}
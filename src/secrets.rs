@@ -0,0 +1,260 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::api::{GLApiError, GLApiResult};
+
+const STORE_PLAIN: &str = "plain";
+const STORE_KEYRING: &str = "keyring";
+const STORE_FILE: &str = "file";
+
+pub const POSSIBLE_STORES: [&str; 3] = [STORE_PLAIN, STORE_KEYRING, STORE_FILE];
+
+/// Environment variable with the passphrase of encrypted secrets file
+pub const PASSPHRASE_ENV: &str = "MERGEREQ_PASSPHRASE";
+
+/// Where private tokens are kept
+#[derive(Copy, Clone, PartialEq)]
+pub enum TokenStore {
+  /// Plain text in the global config
+  Plain,
+  /// OS secret service (Secret Service API on Linux, Keychain on macOS, Credential Manager on Windows)
+  Keyring,
+  /// File encrypted with passphrase from `MERGEREQ_PASSPHRASE`
+  File,
+}
+
+impl TokenStore {
+  /// Secure store if mergereq is built with `secret-store` feature, plain text otherwise
+  pub fn default_for_build() -> Self {
+    if cfg!(feature = "secret-store") {
+      TokenStore::Keyring
+    } else {
+      TokenStore::Plain
+    }
+  }
+}
+
+impl FromStr for TokenStore {
+  type Err = GLApiError;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      STORE_PLAIN => Ok(TokenStore::Plain),
+      STORE_KEYRING => Ok(TokenStore::Keyring),
+      STORE_FILE => Ok(TokenStore::File),
+      _ => Err(GLApiError::ParseError(s.to_owned())),
+    }
+  }
+}
+
+impl fmt::Display for TokenStore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let s = match self {
+      TokenStore::Plain => STORE_PLAIN,
+      TokenStore::Keyring => STORE_KEYRING,
+      TokenStore::File => STORE_FILE,
+    };
+    write!(f, "{}", s)
+  }
+}
+
+/// Parses reference to token stored outside of config, e.g. `keyring:gitlab.example.com`
+pub fn parse_token_ref(token_ref: &str) -> GLApiResult<(TokenStore, &str)> {
+  let mut parts = token_ref.splitn(2, ':');
+  let store = TokenStore::from_str(parts.next().unwrap_or_default())?;
+  match (store, parts.next()) {
+    (TokenStore::Plain, _) | (_, None) => Err(GLApiError::ParseError(token_ref.to_owned()).into()),
+    (store, Some(account)) => Ok((store, account)),
+  }
+}
+
+/// Access to tokens kept in OS secret service or encrypted file
+pub struct Secrets {
+  file_path: String,
+}
+
+impl Secrets {
+  pub fn new(file_path: String) -> Self {
+    Secrets { file_path }
+  }
+
+  /// Stores token and returns reference to it that should be saved in config
+  pub fn save(&self, store: TokenStore, account: &str, token: &str) -> GLApiResult<String> {
+    match store {
+      TokenStore::Plain => return Err(GLApiError::ParseError(store.to_string()).into()),
+      TokenStore::Keyring => backend::keyring_set(account, token)?,
+      TokenStore::File => backend::file_set(&self.file_path, account, token)?,
+    }
+    Ok(format!("{}:{}", store, account))
+  }
+
+  pub fn load(&self, token_ref: &str) -> GLApiResult<String> {
+    match parse_token_ref(token_ref)? {
+      (TokenStore::Keyring, account) => backend::keyring_get(account),
+      (_, account) => backend::file_get(&self.file_path, account),
+    }
+  }
+
+  pub fn delete(&self, token_ref: &str) -> GLApiResult<()> {
+    match parse_token_ref(token_ref)? {
+      (TokenStore::Keyring, account) => backend::keyring_delete(account),
+      (_, account) => backend::file_delete(&self.file_path, account),
+    }
+  }
+}
+
+#[cfg(feature = "secret-store")]
+mod backend {
+  use super::PASSPHRASE_ENV;
+  use crate::api::{GLApiError, GLApiResult};
+  use crate::configs::write_private;
+
+  use aes_gcm::aead::{Aead, KeyInit};
+  use aes_gcm::{Aes256Gcm, Key, Nonce};
+  use base64::{engine::general_purpose::STANDARD, Engine};
+  use rand::RngCore;
+  use std::collections::BTreeMap;
+  use std::fs;
+
+  const KEYRING_SERVICE: &str = "mergereq";
+  const SALT_LEN: usize = 16;
+  const NONCE_LEN: usize = 12;
+  const PBKDF2_ROUNDS: u32 = 100_000;
+
+  fn secret_err<E: std::fmt::Display>(e: E) -> failure::Error {
+    GLApiError::SecretStore(e.to_string()).into()
+  }
+
+  fn keyring_entry(account: &str) -> GLApiResult<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, account).map_err(secret_err)
+  }
+
+  pub fn keyring_set(account: &str, token: &str) -> GLApiResult<()> {
+    keyring_entry(account)?
+      .set_password(token)
+      .map_err(secret_err)
+  }
+
+  pub fn keyring_get(account: &str) -> GLApiResult<String> {
+    keyring_entry(account)?.get_password().map_err(secret_err)
+  }
+
+  pub fn keyring_delete(account: &str) -> GLApiResult<()> {
+    match keyring_entry(account)?.delete_password() {
+      Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+      Err(e) => Err(secret_err(e)),
+    }
+  }
+
+  fn passphrase() -> GLApiResult<String> {
+    std::env::var(PASSPHRASE_ENV).map_err(|_| {
+      let msg = format!(
+        "passphrase of encrypted file is not set in `{}`",
+        PASSPHRASE_ENV
+      );
+      GLApiError::SecretStore(msg).into()
+    })
+  }
+
+  fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+  }
+
+  fn read_file(path: &str) -> GLApiResult<BTreeMap<String, String>> {
+    match fs::read_to_string(path) {
+      Ok(s) => Ok(toml::from_str(&s)?),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  fn write_file(path: &str, secrets: &BTreeMap<String, String>) -> GLApiResult<()> {
+    if secrets.is_empty() {
+      let _ = fs::remove_file(path);
+      return Ok(());
+    }
+    write_private(path, &toml::ser::to_vec(secrets)?)
+  }
+
+  /// Each token is stored as base64 of `salt | nonce | ciphertext`
+  pub fn file_set(path: &str, account: &str, token: &str) -> GLApiResult<()> {
+    let passphrase = passphrase()?;
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(&passphrase, &salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let encrypted = cipher
+      .encrypt(Nonce::from_slice(&nonce), token.as_bytes())
+      .map_err(secret_err)?;
+
+    let mut data = Vec::with_capacity(SALT_LEN + NONCE_LEN + encrypted.len());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&encrypted);
+
+    let mut secrets = read_file(path)?;
+    secrets.insert(account.to_owned(), STANDARD.encode(data));
+    write_file(path, &secrets)
+  }
+
+  pub fn file_get(path: &str, account: &str) -> GLApiResult<String> {
+    let secrets = read_file(path)?;
+    let encoded = secrets
+      .get(account)
+      .ok_or_else(|| secret_err(format!("no token for `{}` in `{}`", account, path)))?;
+    let data = STANDARD.decode(encoded).map_err(secret_err)?;
+    if data.len() <= SALT_LEN + NONCE_LEN {
+      return Err(secret_err("encrypted token is corrupted"));
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, encrypted) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(&passphrase()?, salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let token = cipher
+      .decrypt(Nonce::from_slice(nonce), encrypted)
+      .map_err(|_| secret_err("cannot decrypt token, passphrase is wrong"))?;
+    String::from_utf8(token).map_err(secret_err)
+  }
+
+  pub fn file_delete(path: &str, account: &str) -> GLApiResult<()> {
+    let mut secrets = read_file(path)?;
+    if secrets.remove(account).is_some() {
+      write_file(path, &secrets)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(not(feature = "secret-store"))]
+mod backend {
+  use crate::api::{GLApiError, GLApiResult};
+
+  fn unavailable<T>() -> GLApiResult<T> {
+    let msg = "mergereq is built without `secret-store` feature".to_owned();
+    Err(GLApiError::SecretStore(msg).into())
+  }
+
+  pub fn keyring_set(_account: &str, _token: &str) -> GLApiResult<()> {
+    unavailable()
+  }
+  pub fn keyring_get(_account: &str) -> GLApiResult<String> {
+    unavailable()
+  }
+  pub fn keyring_delete(_account: &str) -> GLApiResult<()> {
+    unavailable()
+  }
+  pub fn file_set(_path: &str, _account: &str, _token: &str) -> GLApiResult<()> {
+    unavailable()
+  }
+  pub fn file_get(_path: &str, _account: &str) -> GLApiResult<String> {
+    unavailable()
+  }
+  pub fn file_delete(_path: &str, _account: &str) -> GLApiResult<()> {
+    unavailable()
+  }
+}
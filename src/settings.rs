@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::api::{ConnectionSettings, ReqParams, ResponseCache, RetryPolicy, EXPIRY_WARNING_DAYS};
+use crate::configs::{Configs, EnvVars, HostData, ANY_HOST};
+use crate::remote::{self, RemoteInfo};
+
+static DEFAULT_REMOTE: &str = "origin";
+/// Source of token saved by `login`
+static LOGIN_SOURCE: &str = "login";
+static REMOTE_SOURCE: &str = "git remote";
+/// Sources of tokens saved in the global config
+static CONFIG_SOURCES: [&str; 2] = ["global config", LOGIN_SOURCE];
+
+/// Settings passed explicitly, e.g. with command line options.
+/// They take precedence over environment, configs and git remote
+#[derive(Default, Clone)]
+pub struct Overrides {
+  pub private_token: Option<String>,
+  pub repo_url: Option<String>,
+  pub project: Option<String>,
+  /// Name of git remote to infer `repo_url` and project from. Default is `origin`
+  pub remote: Option<String>,
+  pub retries: Option<u32>,
+  pub request_budget: Option<u32>,
+  pub retry_non_idempotent: bool,
+  /// Seconds
+  pub connect_timeout: Option<u64>,
+  /// Seconds
+  pub timeout: Option<u64>,
+  pub proxy: Option<String>,
+  pub no_proxy: Option<String>,
+  pub ca_cert: Option<String>,
+  pub client_identity: Option<String>,
+  /// Disables TLS certificate validation. Dangerous
+  pub insecure: bool,
+  /// Max count of concurrent requests, used with `async` feature
+  pub concurrency: Option<usize>,
+}
+
+/// Name of git remote from overrides or local config. Default is `origin`
+pub fn remote_name<'a>(cfg: &'a Configs, overrides: &'a Overrides) -> &'a str {
+  overrides
+    .remote
+    .as_deref()
+    .or_else(|| cfg.local.as_ref().and_then(|loc| loc.remote.as_deref()))
+    .unwrap_or(DEFAULT_REMOTE)
+}
+
+/// Infers GitLab URL and project from the git remote,
+/// if they are not specified with overrides, environment or in the local config
+pub fn detect_remote(cfg: &Configs, env: &EnvVars, overrides: &Overrides) -> Option<RemoteInfo> {
+  let local_ref = cfg.local.as_ref();
+  let has_repo_url = overrides.repo_url.is_some()
+    || env.repo_url.is_some()
+    || env.ci_server_url.is_some()
+    || local_ref.is_some_and(|loc| loc.repo_url.is_some());
+  let has_project = overrides.project.is_some()
+    || env.project.is_some()
+    || env.ci_project_path.is_some()
+    || local_ref.is_some_and(|loc| loc.default_project.is_some());
+  if has_repo_url && has_project {
+    return None;
+  }
+
+  let name = remote_name(cfg, overrides);
+  let remote_url = remote::get_remote_url(name)?;
+
+  let empty_hosts = HashMap::new();
+  let ssh_hosts = cfg.global.as_ref().map_or(&empty_hosts, |g| &g.ssh_hosts);
+  let info = remote::parse_remote_url(&remote_url, ssh_hosts)?;
+  info!(
+    "GitLab {} and project {} are inferred from git remote `{}` ({})",
+    info.repo_url, info.project, name, remote_url
+  );
+  Some(info)
+}
+
+/// Collects request parameters. Every parameter is taken from the first source it is found in:
+/// * project: overrides, `MERGEREQ_PROJECT`, local config, `CI_PROJECT_PATH`, git remote
+/// * repo_url: overrides, `MERGEREQ_REPO_URL`, local config, `CI_SERVER_URL`, git remote
+/// * token: overrides, `MERGEREQ_TOKEN`, global config (private token or tokens of `login`),
+///   `CI_JOB_TOKEN`
+///
+/// Settings of the `*` host of the global config are used only for `repo_url` given explicitly.
+/// When it is inferred from git remote, the host needs its own entry
+pub fn resolve_req_params(cfg: &Configs, env: &EnvVars, overrides: &Overrides) -> ReqParams {
+  let remote_info = detect_remote(cfg, env, overrides);
+  let remote_info = remote_info.as_ref();
+  let global_ref = cfg.global.as_ref();
+  let local_ref = cfg.local.as_ref();
+
+  let project = sourced(overrides.project.as_deref(), "command line")
+    .or(sourced(env.project.as_deref(), "MERGEREQ_PROJECT"))
+    .or_else(|| {
+      sourced(
+        local_ref.and_then(|loc| loc.default_project.as_deref()),
+        "local config",
+      )
+    })
+    .or(sourced(env.ci_project_path.as_deref(), "CI_PROJECT_PATH"))
+    .or_else(|| sourced(remote_info.map(|r| r.project.as_str()), REMOTE_SOURCE));
+  log_source("project", project, true);
+  let project = project.map(|(p, _)| p);
+
+  let repo_url = sourced(overrides.repo_url.as_deref(), "command line")
+    .or(sourced(env.repo_url.as_deref(), "MERGEREQ_REPO_URL"))
+    .or_else(|| {
+      sourced(
+        local_ref.and_then(|loc| loc.repo_url.as_deref()),
+        "local config",
+      )
+    })
+    .or(sourced(env.ci_server_url.as_deref(), "CI_SERVER_URL"))
+    .or_else(|| sourced(remote_info.map(|r| r.repo_url.as_str()), REMOTE_SOURCE));
+  log_source("repo_url", repo_url, true);
+  let inferred = repo_url.is_some_and(|(_, source)| source == REMOTE_SOURCE);
+  let repo_url = repo_url.map(|(u, _)| u);
+
+  // git remote may point to any host, e.g. GitHub, so settings and token of `*`
+  // are used only for URLs given explicitly
+  let host_data = global_ref.and_then(|glob| match repo_url {
+    Some(url) if inferred => glob.exact_host_for_url(url),
+    Some(url) => glob.host_for_url(url),
+    None => glob.hosts.get(ANY_HOST),
+  });
+  let any_host_skipped =
+    inferred && host_data.is_none() && global_ref.is_some_and(|g| g.hosts.contains_key(ANY_HOST));
+
+  let token = sourced(overrides.private_token.as_deref(), "command line")
+    .or(sourced(env.token.as_deref(), "MERGEREQ_TOKEN"))
+    .or_else(|| sourced(host_data.and_then(|h| cfg.host_token(h)), "global config"))
+    .or_else(|| {
+      let oauth = host_data.and_then(|h| cfg.host_oauth_token(h));
+      sourced(oauth.map(|t| t.access_token.as_str()), LOGIN_SOURCE)
+    });
+  if token.is_none() && any_host_skipped {
+    warn!(
+      "{} is inferred from git remote and has no settings in the global config, \
+       the `*` token is not sent to it. Pass --repo-url or save a token with --host",
+      repo_url.unwrap_or_default()
+    );
+  }
+  let oauth = token.is_some_and(|(_, source)| source == LOGIN_SOURCE);
+  let from_config = token.is_some_and(|(_, source)| CONFIG_SOURCES.contains(&source));
+  if let Some(url) = repo_url.filter(|u| from_config && u.starts_with("http://")) {
+    warn!(
+      "!!! Token from the global config is sent over plain HTTP to {} !!! \
+       Anyone on the network can intercept it",
+      url
+    );
+  }
+  log_source(
+    "private token",
+    token.or(sourced(env.ci_job_token.as_deref(), "CI_JOB_TOKEN")),
+    false,
+  );
+  let token = token.map(|(t, _)| t);
+  let job_token = token.is_none() && env.ci_job_token.is_some();
+
+  let mut retry = RetryPolicy::new().retry_non_idempotent(overrides.retry_non_idempotent);
+  if let Some(r) = overrides
+    .retries
+    .or_else(|| host_data.and_then(|h| h.retries))
+  {
+    retry = retry.max_retries(r);
+  }
+  let budget = overrides
+    .request_budget
+    .or_else(|| host_data.and_then(|h| h.request_budget));
+  if let Some(b) = budget {
+    retry = retry.request_budget(b);
+  }
+
+  let connection = resolve_connection(env, overrides, host_data, repo_url);
+  debug!("{:?}", connection);
+  debug!("{:?}", retry);
+
+  ReqParams {
+    private_token: token.or(env.ci_job_token.as_deref()).map(str::to_owned),
+    repo_url: repo_url.map(str::to_owned),
+    default_project: project.map(str::to_owned),
+    api_version: host_data.and_then(|h| h.api_version.clone()),
+    connection,
+    job_token,
+    oauth,
+    retry,
+    concurrency: overrides.concurrency,
+  }
+}
+
+/// Cache of responses in `MERGEREQ_CACHE_DIR`, or in the default cache dir of the user
+pub fn resolve_cache(env: &EnvVars) -> Option<ResponseCache> {
+  let dir = env
+    .cache_dir
+    .as_ref()
+    .map(PathBuf::from)
+    .or_else(ResponseCache::default_dir)?;
+  debug!("Response cache is in {}", dir.display());
+  Some(ResponseCache::new(dir))
+}
+
+/// Count of days before expiry of the private token when commands start to warn,
+/// from settings of host of `repo_url` in the global config
+pub fn token_expiry_warning_days(cfg: &Configs, repo_url: Option<&str>) -> u32 {
+  cfg
+    .global
+    .as_ref()
+    .and_then(|glob| match repo_url {
+      Some(url) => glob.host_for_url(url),
+      None => glob.hosts.get(ANY_HOST),
+    })
+    .and_then(|h| h.token_expiry_warning_days)
+    .unwrap_or(EXPIRY_WARNING_DAYS)
+}
+
+/// Pairs setting with the name of its source
+fn sourced<'a>(value: Option<&'a str>, source: &'static str) -> Option<(&'a str, &'static str)> {
+  value.map(|v| (v, source))
+}
+
+/// Logs where setting is taken from. Value is logged only if it is not secret
+fn log_source(name: &str, setting: Option<(&str, &str)>, show_value: bool) {
+  match setting {
+    Some((value, source)) if show_value => info!("{} `{}` is taken from {}", name, value, source),
+    Some((_, source)) => info!("{} is taken from {}", name, source),
+    None => info!("{} is not set", name),
+  }
+}
+
+/// Collects connection settings. Overrides take precedence over settings of host
+/// in the global config, which take precedence over environment variables
+fn resolve_connection(
+  env: &EnvVars,
+  overrides: &Overrides,
+  host_data: Option<&HostData>,
+  repo_url: Option<&str>,
+) -> ConnectionSettings {
+  let host_str = |f: fn(&HostData) -> &Option<String>| host_data.and_then(|h| f(h).clone());
+  let host_secs = |f: fn(&HostData) -> Option<u64>| host_data.and_then(f);
+
+  let env_proxy = if repo_url.is_some_and(|u| u.starts_with("http://")) {
+    env.http_proxy.clone()
+  } else {
+    env.https_proxy.clone()
+  };
+
+  let accept_invalid_certs =
+    overrides.insecure || host_data.and_then(|h| h.accept_invalid_certs) == Some(true);
+  if accept_invalid_certs {
+    warn!(
+      "!!! TLS certificate verification is DISABLED for {} !!! \
+       Anyone on the network can intercept your private token",
+      repo_url.unwrap_or("GitLab")
+    );
+  }
+
+  ConnectionSettings {
+    connect_timeout: overrides
+      .connect_timeout
+      .or_else(|| host_secs(|h| h.connect_timeout))
+      .map(Duration::from_secs),
+    timeout: overrides
+      .timeout
+      .or_else(|| host_secs(|h| h.timeout))
+      .map(Duration::from_secs),
+    proxy: overrides
+      .proxy
+      .clone()
+      .or_else(|| host_str(|h| &h.proxy))
+      .or(env_proxy),
+    no_proxy: overrides
+      .no_proxy
+      .clone()
+      .or_else(|| host_str(|h| &h.no_proxy))
+      .or_else(|| env.no_proxy.clone()),
+    ca_cert: overrides
+      .ca_cert
+      .clone()
+      .or_else(|| host_str(|h| &h.ca_cert)),
+    client_identity: overrides
+      .client_identity
+      .clone()
+      .or_else(|| host_str(|h| &h.client_identity)),
+    client_identity_password: host_str(|h| &h.client_identity_password)
+      .or_else(|| env.identity_password.clone()),
+    accept_invalid_certs,
+  }
+}
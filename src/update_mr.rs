@@ -0,0 +1,97 @@
+use super::{create_mr, helpers, ls};
+use crate::api::{GLApi, GLApiResult, MRStateEvent, MergeRequest, UpdateMRBody};
+use crate::output::OutputFormat;
+use clap::ArgMatches;
+use std::str::FromStr;
+
+fn parse_num_or_exit(val: &str, what: &str) -> u32 {
+  val.trim().parse().unwrap_or_else(|err| {
+    eprintln!(
+      "[ERROR] You specify {}, but it is not a valid id. {}",
+      what, err
+    );
+    std::process::exit(1);
+  })
+}
+
+pub fn fill_mr_update_data<'a>(glapi: &GLApi, args_matches: &'a ArgMatches) -> UpdateMRBody<'a> {
+  let assignee_ids = if let Some(ids) = args_matches.values_of("assignee-id") {
+    let ids: Vec<u32> = ids
+      .map(|id| parse_num_or_exit(id, "assignee id"))
+      .filter(|id| *id != 0)
+      .collect();
+    Some(ids)
+  } else {
+    create_mr::get_assignee_id(glapi, None, args_matches.value_of("assignee-name"))
+      .map(|id| vec![id])
+  };
+
+  let parse_bool = |name: &str| args_matches.value_of(name).map(|v| v == "true");
+
+  UpdateMRBody {
+    title: args_matches.value_of("title"),
+    description: args_matches.value_of("description"),
+    target_branch: args_matches.value_of("target-branch"),
+    assignee_ids,
+    labels: args_matches.value_of("labels"),
+    milestone_id: args_matches
+      .value_of("milestone-id")
+      .map(|v| parse_num_or_exit(v, "milestone id")),
+    state_event: args_matches
+      .value_of("state-event")
+      .and_then(|v| MRStateEvent::from_str(v).ok()),
+    remove_source_branch: parse_bool("remove-source-branch"),
+    squash: parse_bool("squash"),
+  }
+}
+
+fn print_change<T: std::fmt::Display>(name: &str, val: Option<T>) {
+  if let Some(v) = val {
+    eprintln!("  {:<15}— {}", name, v);
+  }
+}
+
+pub fn confirm_update(mr: &MergeRequest, data: &UpdateMRBody) {
+  if data.is_empty() {
+    eprintln!("[ERROR] Nothing to update. Specify at least one attribute to change");
+    std::process::exit(1);
+  }
+
+  eprintln!(
+    "You updating merge request !{} ({}) with this parameters:",
+    mr.iid,
+    helpers::get_one_line(&mr.title)
+  );
+  print_change("Title:", data.title.map(helpers::get_one_line));
+  print_change("Description:", data.description.map(helpers::get_one_line));
+  print_change("Target branch:", data.target_branch);
+  let assignees = data.assignee_ids.as_ref().map(|ids| {
+    if ids.is_empty() {
+      "None".to_owned()
+    } else {
+      let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+      format!("(ID: {})", ids.join(", "))
+    }
+  });
+  print_change("Assignees:", assignees);
+  print_change("Labels:", data.labels);
+  print_change("Milestone ID:", data.milestone_id);
+  print_change("State:", data.state_event);
+  print_change("Remove source:", data.remove_source_branch);
+  print_change("Squash:", data.squash);
+
+  helpers::confirm_or_exit();
+}
+
+pub fn log_updated_mr(mr: &MergeRequest, fmt: OutputFormat) -> GLApiResult<()> {
+  if !fmt.is_table() {
+    return ls::single_mr(mr, fmt);
+  }
+  println!(
+    "\nMerge request !{} is updated. You can see it here:",
+    mr.iid
+  );
+  println!("{}", mr.web_url);
+  println!("State: {}", mr.state);
+  Ok(())
+}
@@ -0,0 +1,360 @@
+mod common;
+
+use common::{api_error, FakeGitLab, BRANCHES_COUNT, MAX_PER_PAGE, PROJECT};
+use mergereq::api::{
+  CreateMRBody, GLApi, GLApiError, GetBranchesQuery, GetMergeRequestsQuery, GetProjectsQuery,
+  GetUsersQuery, MRState, MergeMRBody, Pagination, ResponseCache, RetryPolicy, UpdateMRBody,
+};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+fn create_body<'a>(title: &str, source_branch: &str) -> CreateMRBody<'a> {
+  CreateMRBody {
+    id: PROJECT.to_owned(),
+    source_branch: source_branch.to_owned(),
+    target_branch: "master".to_owned(),
+    title: title.to_owned(),
+    assignee_id: None,
+    description: Some("Description"),
+    remove_source_branch: Some(true),
+    squash: None,
+  }
+}
+
+#[test]
+fn projects_follow_keyset_links() {
+  let fake = FakeGitLab::new();
+  let projects = fake
+    .api()
+    .get_projects(&GetProjectsQuery::new(), &Pagination::new().limit(2))
+    .unwrap();
+  assert_eq!(projects.len(), 2);
+
+  let projects = fake
+    .api()
+    .get_projects(&GetProjectsQuery::new(), &Pagination::new())
+    .unwrap();
+  let ids: Vec<u32> = projects.iter().map(|p| p.id).collect();
+  assert_eq!(ids, vec![3, 2, 1]);
+  let first = &fake.requests()[1];
+  assert_eq!(first.query_value("pagination"), Some("keyset"));
+  assert_eq!(first.query_value("order_by"), Some("id"));
+}
+
+#[test]
+fn project_is_requested_by_encoded_path() {
+  let fake = FakeGitLab::new();
+  let project = fake.api().get_project("group/other").unwrap();
+  assert_eq!(project.id, 2);
+  assert_eq!(fake.requests()[0].path, "/projects/group/other");
+
+  let err = api_error(fake.api().get_project("group/missing"));
+  assert!(matches!(err, GLApiError::NotFound { .. }));
+}
+
+#[test]
+fn users_are_filtered() {
+  let fake = FakeGitLab::new();
+  let query = GetUsersQuery::new().username("alice");
+  let users = fake.api().get_users(&query, &Pagination::new()).unwrap();
+  assert_eq!(users.len(), 1);
+  assert_eq!(users[0].id, 2);
+  assert_eq!(fake.requests()[0].query_value("username"), Some("alice"));
+}
+
+#[test]
+fn branches_are_fetched_from_all_pages() {
+  let fake = FakeGitLab::new();
+  let branches = fake
+    .api()
+    .get_project_branches(None, &GetBranchesQuery::new(), &Pagination::new())
+    .unwrap();
+  assert_eq!(branches.len(), BRANCHES_COUNT);
+  assert_eq!(branches[0].name, "master");
+  assert_eq!(fake.requests().len(), BRANCHES_COUNT.div_ceil(MAX_PER_PAGE));
+}
+
+#[test]
+fn branches_limit_and_page() {
+  let fake = FakeGitLab::new();
+  let api = fake.api();
+  let query = GetBranchesQuery::new();
+
+  let limited = api
+    .get_project_branches(None, &query, &Pagination::new().limit(25))
+    .unwrap();
+  assert_eq!(limited.len(), 25);
+  assert_eq!(fake.requests().len(), 2);
+
+  let page = api
+    .get_project_branches(None, &query, &Pagination::new().page(3))
+    .unwrap();
+  assert_eq!(page.len(), BRANCHES_COUNT - 2 * MAX_PER_PAGE);
+  assert_eq!(page[0].name, format!("feature-{}", 2 * MAX_PER_PAGE));
+}
+
+#[test]
+fn merge_requests_are_filtered_by_state() {
+  let fake = FakeGitLab::new();
+  let query = GetMergeRequestsQuery::new().state(MRState::Merged);
+  let mrs = fake
+    .api()
+    .get_project_merge_requests(None, &query, &Pagination::new())
+    .unwrap();
+  assert_eq!(mrs.len(), 1);
+  assert_eq!(mrs[0].source_branch, "update-deps");
+
+  let mrs = fake
+    .api()
+    .get_merge_requests_of_projects(
+      &["group/project", "group/other"],
+      &GetMergeRequestsQuery::new(),
+      &Pagination::new(),
+    )
+    .unwrap();
+  let projects: Vec<&str> = mrs.iter().map(|(p, _)| p.as_str()).collect();
+  assert_eq!(projects, vec!["group/project", "group/other"]);
+}
+
+#[test]
+fn merge_request_is_created() {
+  let fake = FakeGitLab::new();
+  let mr = fake
+    .api()
+    .create_merge_request(PROJECT, &create_body("New feature", "feature-7"))
+    .unwrap();
+  assert_eq!(mr.iid, 4);
+  assert_eq!(mr.title, "New feature");
+
+  let req = fake.requests().pop().unwrap();
+  assert_eq!(req.method, "POST");
+  assert_eq!(req.path, "/projects/group/project/merge_requests");
+  let body = req.body.unwrap();
+  assert_eq!(body["source_branch"], "feature-7");
+  assert_eq!(body["remove_source_branch"], true);
+
+  let shown = fake.api().get_merge_request(None, 4).unwrap();
+  assert_eq!(shown.description.as_deref(), Some("Description"));
+}
+
+#[test]
+fn validation_errors_are_reported_by_field() {
+  let fake = FakeGitLab::new();
+  let err = fake
+    .api()
+    .create_merge_request(PROJECT, &create_body("", "no-such-branch"));
+  match api_error(err) {
+    GLApiError::Validation { status, errors, .. } => {
+      assert_eq!(status, 422);
+      assert_eq!(errors["title"], vec!["can't be blank"]);
+      assert!(errors.contains_key("source_branch"));
+    }
+    e => panic!("unexpected error: {}", e),
+  }
+
+  let body = UpdateMRBody {
+    title: Some(""),
+    ..UpdateMRBody::default()
+  };
+  let err = fake.api().update_merge_request(None, 1, &body);
+  assert!(matches!(api_error(err), GLApiError::Validation { .. }));
+}
+
+#[test]
+fn missing_merge_request_is_not_found() {
+  let fake = FakeGitLab::new();
+  let err = fake.api().get_merge_request(None, 999);
+  assert!(matches!(api_error(err), GLApiError::NotFound { .. }));
+}
+
+#[test]
+fn proxy_error_page_is_not_message() {
+  let page = "<html>\n<head><title>502 Bad Gateway</title></head>\n<body>nginx</body>\n</html>\n";
+  let err = GLApiError::from_status(502, None, page.to_owned());
+  assert_eq!(err.to_string(), "Server error (502): Bad Gateway");
+  assert_eq!(err.body(), Some(page));
+
+  let text = format!("upstream timed out {}\nsecond line", "x".repeat(200));
+  let message = GLApiError::from_status(504, None, text)
+    .message()
+    .unwrap()
+    .to_owned();
+  assert!(message.starts_with("upstream timed out"), "{}", message);
+  assert!(message.ends_with("..."), "{}", message);
+  assert!(message.len() < 110, "{}", message);
+}
+
+#[test]
+fn wrong_token_is_unauthorized() {
+  let fake = FakeGitLab::new();
+  let api = GLApi::with_transport(fake.req_params().private_token("wrong"), fake.clone());
+  let err = api.get_project(PROJECT);
+  assert!(matches!(api_error(err), GLApiError::Unauthorized { .. }));
+}
+
+#[test]
+fn bad_gateway_is_retried() {
+  let fake = FakeGitLab::new();
+  fake.fail_next(1);
+  let project = fake.api().get_project(PROJECT).unwrap();
+  assert_eq!(project.id, 1);
+  assert_eq!(fake.requests().len(), 2);
+
+  fake.fail_next(1);
+  let params = fake.req_params().retry(RetryPolicy::new().max_retries(0));
+  let err = GLApi::with_transport(params, fake.clone()).get_project(PROJECT);
+  assert!(matches!(
+    api_error(err),
+    GLApiError::ServerError { status: 502, .. }
+  ));
+}
+
+#[test]
+fn retry_after_is_not_shortened() {
+  let fake = FakeGitLab::new();
+  fake.rate_limit_next(1, 1);
+  let started = Instant::now();
+  assert_eq!(fake.api().get_project(PROJECT).unwrap().id, 1);
+  assert!(started.elapsed() >= Duration::from_secs(1));
+  assert_eq!(fake.requests().len(), 2);
+
+  // too long delay is not waited at all
+  fake.rate_limit_next(1, 3600);
+  let params = fake
+    .req_params()
+    .retry(RetryPolicy::new().max_server_delay(Duration::from_secs(60)));
+  let err = GLApi::with_transport(params, fake.clone()).get_project(PROJECT);
+  assert!(matches!(
+    api_error(err),
+    GLApiError::RateLimited {
+      retry_after: Some(3600),
+      ..
+    }
+  ));
+  assert_eq!(fake.requests().len(), 3);
+}
+
+#[test]
+fn merge_is_not_retried() {
+  let fake = FakeGitLab::new();
+  fake.fail_next(1);
+  let err = fake
+    .api()
+    .merge_merge_request(None, 1, &MergeMRBody::default());
+  assert!(matches!(
+    api_error(err),
+    GLApiError::ServerError { status: 502, .. }
+  ));
+  assert_eq!(fake.requests().len(), 1);
+}
+
+#[test]
+fn request_budget_stops_pagination() {
+  let fake = FakeGitLab::new();
+  let params = fake
+    .req_params()
+    .retry(RetryPolicy::new().request_budget(2));
+  let err = GLApi::with_transport(params, fake.clone()).get_project_branches(
+    None,
+    &GetBranchesQuery::new(),
+    &Pagination::new(),
+  );
+  assert!(matches!(
+    api_error(err),
+    GLApiError::RequestBudgetExceeded { budget: 2 }
+  ));
+  assert_eq!(fake.requests().len(), 2);
+}
+
+#[test]
+fn project_is_cached_and_revalidated() {
+  let fake = FakeGitLab::new();
+  let dir =
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("api-cache-{}", std::process::id()));
+  let cache = ResponseCache::new(dir);
+  cache.clear().unwrap();
+
+  let api = fake.api().cache(cache.clone());
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  assert_eq!(fake.requests().len(), 1);
+
+  let api = fake
+    .api()
+    .cache(cache.clone().project_ttl(Duration::from_secs(0)));
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  let requests = fake.requests();
+  assert_eq!(requests.len(), 2);
+  assert_eq!(requests[1].status, 304);
+
+  // branches change often and are not cached
+  let pagination = Pagination::new().limit(1);
+  for _ in 0..2 {
+    api
+      .get_project_branches(None, &GetBranchesQuery::new(), &pagination)
+      .unwrap();
+  }
+  assert_eq!(fake.requests().len(), 4);
+  assert_eq!(cache.clear().unwrap(), 1);
+}
+
+#[test]
+fn cache_is_per_token() {
+  let fake = FakeGitLab::new();
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+    .join(format!("api-cache-token-{}", std::process::id()));
+  let cache = ResponseCache::new(dir);
+  cache.clear().unwrap();
+
+  assert_eq!(
+    fake
+      .api()
+      .cache(cache.clone())
+      .get_project(PROJECT)
+      .unwrap()
+      .id,
+    1
+  );
+  // project fetched with another token is not served to a token without access
+  let params = fake.req_params().private_token("wrong");
+  let api = GLApi::with_transport(params, fake.clone()).cache(cache.clone());
+  assert!(matches!(
+    api_error(api.get_project(PROJECT)),
+    GLApiError::Unauthorized { .. }
+  ));
+  assert_eq!(fake.requests().len(), 2);
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |p: &std::path::Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(cache.dir()), 0o700);
+    for entry in fs::read_dir(cache.dir()).unwrap() {
+      assert_eq!(mode(&entry.unwrap().path()), 0o600);
+    }
+  }
+  assert_eq!(cache.clear().unwrap(), 1);
+}
+
+#[test]
+fn token_warnings() {
+  let fake = FakeGitLab::new();
+  let api = fake.api();
+  assert_eq!(api.get_current_user().unwrap().username, "admin");
+  assert!(api.get_current_token().unwrap().warnings(7).is_empty());
+
+  fake.set_token_info(&["api"], Some(3));
+  let token = api.get_current_token().unwrap();
+  assert_eq!(token.days_until_expiry(), Some(3));
+  let warnings = token.warnings(7);
+  assert_eq!(warnings.len(), 1);
+  assert!(warnings[0].contains("expires in 3 days"), "{:?}", warnings);
+  assert!(token.warnings(2).is_empty());
+
+  fake.set_token_info(&["read_api"], Some(-1));
+  let warnings = api.get_current_token().unwrap().warnings(7);
+  assert_eq!(warnings.len(), 2);
+  assert!(warnings[0].contains("is expired"), "{:?}", warnings);
+  assert!(warnings[1].contains("no `api` scope"), "{:?}", warnings);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{api_error, FakeGitLab, PROJECT, TOKEN};
+use mergereq::api::{
+  Cassette, GLApi, GLApiError, GLApiResult, GetBranchesQuery, GetMergeRequestsQuery, HttpRequest,
+  HttpResponse, Pagination, RecordingTransport, ReplayTransport, Transport,
+};
+use reqwest::header::{HeaderMap, HeaderValue, LINK};
+use reqwest::{Method, StatusCode};
+use std::fs;
+use std::path::PathBuf;
+
+fn cassette_path(name: &str) -> PathBuf {
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+  dir.join(format!("{}-{}.json", name, std::process::id()))
+}
+
+#[test]
+fn recorded_traffic_is_replayed() {
+  let fake = FakeGitLab::new();
+  let path = cassette_path("replayed");
+  let recording = RecordingTransport::new(fake.clone(), &path);
+  let api = GLApi::with_transport(fake.req_params(), recording);
+  let branches = api
+    .get_project_branches(None, &GetBranchesQuery::new(), &Pagination::new())
+    .unwrap();
+  let mr = api.get_merge_request(None, 2).unwrap();
+
+  let cassette = Cassette::load(&path).unwrap();
+  assert_eq!(cassette.interactions.len(), fake.requests().len());
+  assert_eq!(cassette.base_url().as_deref(), Some("https://gitlab.fake"));
+
+  // the same requests are served from cassette, even against another host
+  let params = fake
+    .req_params()
+    .repo_url("https://other.example.com")
+    .private_token("anything");
+  let replay = GLApi::with_transport(params, ReplayTransport::new(cassette));
+  let replayed = replay
+    .get_project_branches(None, &GetBranchesQuery::new(), &Pagination::new())
+    .unwrap();
+  assert_eq!(replayed.len(), branches.len());
+  assert_eq!(replay.get_merge_request(None, 2).unwrap().title, mr.title);
+
+  // every interaction is served once
+  let err = api_error(replay.get_merge_request(None, 2));
+  assert!(matches!(err, GLApiError::ReplayMismatch { .. }));
+}
+
+#[test]
+fn token_is_redacted() {
+  let fake = FakeGitLab::new();
+  let path = cassette_path("redacted");
+  let recording = RecordingTransport::new(fake.clone(), &path);
+  let api = GLApi::with_transport(fake.req_params(), recording);
+  api
+    .get_project_merge_requests(
+      Some(PROJECT),
+      &GetMergeRequestsQuery::new(),
+      &Pagination::new(),
+    )
+    .unwrap();
+
+  let data = fs::read_to_string(&path).unwrap();
+  assert!(!data.contains(TOKEN));
+  let cassette = Cassette::load(&path).unwrap();
+  let headers = &cassette.interactions[0].request.headers;
+  assert_eq!(headers["private-token"], vec!["[REDACTED]"]);
+}
+
+#[test]
+fn errors_are_replayed() {
+  let fake = FakeGitLab::new();
+  let path = cassette_path("errors");
+  let recording = RecordingTransport::new(fake.clone(), &path);
+  let api = GLApi::with_transport(fake.req_params(), recording);
+  assert!(api.get_merge_request(None, 404).is_err());
+
+  let replay = GLApi::with_transport(
+    fake.req_params(),
+    ReplayTransport::new(Cassette::load(&path).unwrap()),
+  );
+  let err = api_error(replay.get_merge_request(None, 404));
+  assert!(matches!(err, GLApiError::NotFound { .. }));
+}
+
+/// Answers every request with two `Link` headers
+struct TwoLinks;
+
+impl Transport for TwoLinks {
+  fn send(&self, _req: &HttpRequest) -> GLApiResult<HttpResponse> {
+    let mut headers = HeaderMap::new();
+    headers.append(LINK, HeaderValue::from_static("<https://a>; rel=\"next\""));
+    headers.append(LINK, HeaderValue::from_static("<https://b>; rel=\"last\""));
+    Ok(HttpResponse {
+      status: StatusCode::OK,
+      headers,
+      body: "[]".to_owned(),
+    })
+  }
+}
+
+#[test]
+fn repeated_headers_are_replayed() {
+  let path = cassette_path("repeated");
+  let req = HttpRequest {
+    method: Method::GET,
+    url: "https://gitlab.fake/api/v4/projects".to_owned(),
+    headers: HeaderMap::new(),
+    body: None,
+  };
+  RecordingTransport::new(TwoLinks, &path).send(&req).unwrap();
+
+  let replayed = ReplayTransport::new(Cassette::load(&path).unwrap())
+    .send(&req)
+    .unwrap();
+  let links: Vec<&HeaderValue> = replayed.headers.get_all(LINK).iter().collect();
+  assert_eq!(links.len(), 2);
+  assert_eq!(links[1], "<https://b>; rel=\"last\"");
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+  }
+}
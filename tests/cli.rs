@@ -0,0 +1,821 @@
+mod common;
+
+use common::{FakeGitLab, AUTH_CODE, BRANCHES_COUNT, CLIENT_ID, PROJECT, TOKEN};
+use reqwest::Url;
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Command running mergereq against fake GitLab in an empty directory with configs
+/// created there, so that neither configs of the user nor git remotes are used.
+/// Token is passed with `--private-token` if it is given
+fn command(repo_url: &str, token: Option<&str>, args: &[&str]) -> Command {
+  let dir = command_dir(repo_url);
+  fs::create_dir_all(&dir).unwrap();
+  let global_cfg = dir.join("global.toml");
+  let local_cfg = dir.join("local.toml");
+  for cfg in [&global_cfg, &local_cfg] {
+    if !cfg.exists() {
+      fs::write(cfg, "").unwrap();
+    }
+  }
+
+  let mut cmd = Command::new(env!("CARGO_BIN_EXE_mergereq"));
+  cmd
+    .current_dir(&dir)
+    .env("HOME", &dir)
+    .args(args)
+    .args(["--global-config", global_cfg.to_str().unwrap()])
+    .args(["--local-config", local_cfg.to_str().unwrap()])
+    .args(["--repo-url", repo_url, "--project", PROJECT])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  if let Some(token) = token {
+    cmd.args(["--private-token", token]);
+  }
+  remove_settings_env(&mut cmd);
+  cmd.env("MERGEREQ_CACHE_DIR", dir.join("cache"));
+  cmd
+}
+
+/// Removes environment variables of the test run that mergereq takes settings from
+fn remove_settings_env(cmd: &mut Command) {
+  for (var, _) in std::env::vars() {
+    if var.starts_with("MERGEREQ_") || var.starts_with("CI_") || var.ends_with("_proxy") {
+      cmd.env_remove(var);
+    }
+  }
+}
+
+/// Directory where `command` runs and keeps its configs, one per fake GitLab
+fn command_dir(repo_url: &str) -> PathBuf {
+  PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!(
+    "cli-{}-{}",
+    std::process::id(),
+    repo_url.rsplit(':').next().unwrap_or_default()
+  ))
+}
+
+/// Runs mergereq with token, writing `stdin` to its input
+fn mergereq(repo_url: &str, token: &str, args: &[&str], stdin: &str) -> Output {
+  let mut child = command(repo_url, Some(token), args)
+    .stdin(Stdio::piped())
+    .spawn()
+    .expect("cannot run mergereq");
+  child
+    .stdin
+    .take()
+    .unwrap()
+    .write_all(stdin.as_bytes())
+    .unwrap();
+  child.wait_with_output().unwrap()
+}
+
+fn stdout_json(out: &Output) -> Value {
+  assert!(
+    out.status.success(),
+    "mergereq failed: {}",
+    String::from_utf8_lossy(&out.stderr)
+  );
+  serde_json::from_slice(&out.stdout).expect("stdout is not JSON")
+}
+
+#[test]
+fn ls_projects() {
+  let url = FakeGitLab::new().serve();
+  let projects = stdout_json(&mergereq(
+    &url,
+    TOKEN,
+    &["ls", "projects", "-o", "json"],
+    "",
+  ));
+  let names: Vec<&str> = projects
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|p| p["name"].as_str().unwrap())
+    .collect();
+  assert_eq!(names, vec!["third", "other", "project"]);
+}
+
+#[test]
+fn ls_users_table() {
+  let url = FakeGitLab::new().serve();
+  let out = mergereq(&url, TOKEN, &["ls", "users", "--username", "alice"], "");
+  let stdout = String::from_utf8_lossy(&out.stdout);
+  assert!(out.status.success());
+  assert!(stdout.contains("Users (1):"), "{}", stdout);
+  assert!(stdout.contains("ALICE"), "{}", stdout);
+}
+
+#[test]
+fn ls_branches_csv() {
+  let url = FakeGitLab::new().serve();
+  let out = mergereq(&url, TOKEN, &["ls", "branches", "-o", "csv"], "");
+  let stdout = String::from_utf8_lossy(&out.stdout);
+  let lines: Vec<&str> = stdout.lines().collect();
+  assert_eq!(lines[0], "short_id,name,author_name");
+  assert_eq!(lines.len(), BRANCHES_COUNT + 1);
+
+  let out = mergereq(
+    &url,
+    TOKEN,
+    &["ls", "branches", "--limit", "5", "-o", "tsv"],
+    "",
+  );
+  assert_eq!(String::from_utf8_lossy(&out.stdout).lines().count(), 6);
+}
+
+#[test]
+fn ls_mr_by_state() {
+  let url = FakeGitLab::new().serve();
+  let args = ["ls", "mr", "--state", "opened", "-o", "json"];
+  let mrs = stdout_json(&mergereq(&url, TOKEN, &args, ""));
+  let iids: Vec<u64> = mrs
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|mr| mr["iid"].as_u64().unwrap())
+    .collect();
+  assert_eq!(iids, vec![1, 2]);
+}
+
+#[test]
+fn create_mr_confirmed() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "feature-3",
+    "-T",
+    "master",
+    "-I",
+    "Add feature",
+    "-o",
+    "json",
+  ];
+  let out = mergereq(&url, TOKEN, &args, "y\n");
+  let mr = stdout_json(&out);
+  assert_eq!(mr["iid"], 4);
+  assert_eq!(mr["title"], "Add feature");
+  assert!(String::from_utf8_lossy(&out.stderr).contains("Source branch: — feature-3"));
+
+  let created = fake
+    .requests()
+    .into_iter()
+    .find(|r| r.method == "POST")
+    .expect("merge request is not created");
+  let body = created.body.unwrap();
+  assert_eq!(body["source_branch"], "feature-3");
+  assert_eq!(body["target_branch"], "master");
+}
+
+#[test]
+fn create_mr_from_template() {
+  let fake = FakeGitLab::new();
+  fake.add_file(
+    ".gitlab/merge_request_templates/feature.md",
+    "## %{title}\n\nMerges %{source_branch} into %{target_branch}.\n",
+  );
+  let url = fake.serve();
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "feature-3",
+    "-T",
+    "master",
+    "-I",
+    "Add feature",
+    "--template",
+    "feature",
+    "-o",
+    "json",
+  ];
+  let mr = stdout_json(&mergereq(&url, TOKEN, &args, "y\n"));
+  assert_eq!(
+    mr["description"],
+    "## Add feature\n\nMerges feature-3 into master.\n"
+  );
+
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "feature-3",
+    "-I",
+    "Add",
+    "--template",
+    "none",
+  ];
+  let out = mergereq(&url, TOKEN, &args, "y\n");
+  assert!(!out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(
+    stderr.contains("Template `none` is found neither"),
+    "{}",
+    stderr
+  );
+}
+
+#[test]
+fn create_mr_composed_in_editor() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+  let seen = dir.join(format!("cli-editor-{}.md", std::process::id()));
+  let editor = dir.join(format!("cli-editor-{}.sh", std::process::id()));
+  fs::write(
+    &editor,
+    "#!/bin/sh\ncp \"$1\" \"$SEEN_BUFFER\"\n\
+     ls -l \"$1\" | cut -c1-10 > \"$SEEN_BUFFER.mode\"\n\
+     printf 'Edited title\\n\\n## Changes\\n\\nDetails\\n' > \"$1\"\n",
+  )
+  .unwrap();
+
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "feature-3",
+    "-T",
+    "master",
+    "-I",
+    "Add feature",
+    "--edit",
+    "-o",
+    "json",
+  ];
+  let mut child = command(&url, Some(TOKEN), &args)
+    .env("VISUAL", format!("sh {}", editor.display()))
+    .env("SEEN_BUFFER", &seen)
+    .stdin(Stdio::piped())
+    .spawn()
+    .unwrap();
+  child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+  let mr = stdout_json(&child.wait_with_output().unwrap());
+  assert_eq!(mr["title"], "Edited title");
+  // markdown headings are kept, only text below the scissors line is dropped
+  assert_eq!(mr["description"], "## Changes\n\nDetails");
+
+  let buffer = fs::read_to_string(&seen).unwrap();
+  assert!(buffer.starts_with("Add feature\n"), "{}", buffer);
+  assert!(buffer.contains("# Source branch: feature-3"), "{}", buffer);
+  // the buffer is readable only by the user
+  let mode = fs::read_to_string(seen.with_extension("md.mode")).unwrap();
+  assert_eq!(mode.trim(), "-rw-------");
+}
+
+/// Runs git in `dir`, returning its trimmed stdout
+fn git(dir: &Path, args: &[&str]) -> String {
+  let out = Command::new("git")
+    .current_dir(dir)
+    .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+    .args(args)
+    .output()
+    .unwrap();
+  assert!(
+    out.status.success(),
+    "{}",
+    String::from_utf8_lossy(&out.stderr)
+  );
+  String::from_utf8_lossy(&out.stdout).trim().to_owned()
+}
+
+/// Git repository with `master` and `branch` branches, the latter with commits of `messages`
+fn git_repo(name: &str, branch: &str, messages: &[&str]) -> PathBuf {
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!(
+    "cli-git-{}-{}",
+    name,
+    std::process::id()
+  ));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  git(&dir, &["init", "-q", "-b", "master"]);
+  git(&dir, &["commit", "-q", "--allow-empty", "-m", "Initial"]);
+  git(&dir, &["checkout", "-q", "-b", branch]);
+  for msg in messages {
+    git(&dir, &["commit", "-q", "--allow-empty", "-m", msg]);
+  }
+  dir
+}
+
+#[test]
+fn create_mr_description_from_commits() {
+  let repo = git_repo(
+    "desc",
+    "feature-4",
+    &[
+      "feat(api): Add labels",
+      "Update readme",
+      "fix: Handle empty title\n\nTitle of one space is rejected",
+      "feat!: Drop v3",
+    ],
+  );
+  let create = |style: &str| {
+    // a new fake each time, as a merge request of the same branches is updated
+    let url = FakeGitLab::new().serve();
+    let args = [
+      "create",
+      "mr",
+      "-S",
+      "feature-4",
+      "-T",
+      "master",
+      "-I",
+      "Add labels",
+      "--desc-from-commits",
+      style,
+      "-o",
+      "json",
+    ];
+    let mut child = command(&url, Some(TOKEN), &args)
+      .current_dir(&repo)
+      .stdin(Stdio::piped())
+      .spawn()
+      .unwrap();
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    stdout_json(&child.wait_with_output().unwrap())["description"].clone()
+  };
+
+  assert_eq!(
+    create("subjects"),
+    "- feat(api): Add labels\n- Update readme\n- fix: Handle empty title\n- feat!: Drop v3"
+  );
+  assert_eq!(
+    create("bodies"),
+    "### feat(api): Add labels\n\n### Update readme\n\n\
+     ### fix: Handle empty title\n\nTitle of one space is rejected\n\n### feat!: Drop v3"
+  );
+  assert_eq!(
+    create("conventional"),
+    "### Features\n\n- **api:** Add labels\n- **BREAKING** Drop v3\n\n\
+     ### Fixes\n\n- Handle empty title\n\n\
+     ### Other changes\n\n- Update readme"
+  );
+}
+
+/// Bare repository added as `origin` of `repo`. It logs push options to `$PUSH_OPTIONS_LOG`
+fn bare_remote(repo: &Path) -> PathBuf {
+  let remote = repo.with_extension("git");
+  let _ = fs::remove_dir_all(&remote);
+  fs::create_dir_all(&remote).unwrap();
+  git(&remote, &["init", "-q", "--bare"]);
+  git(&remote, &["config", "receive.advertisePushOptions", "true"]);
+  let hook = remote.join("hooks").join("pre-receive");
+  fs::write(
+    &hook,
+    "#!/bin/sh\nenv | grep ^GIT_PUSH_OPTION_[0-9] | sort > \"${PUSH_OPTIONS_LOG:-/dev/null}\"\n",
+  )
+  .unwrap();
+  Command::new("chmod").arg("+x").arg(&hook).status().unwrap();
+  git(repo, &["remote", "add", "origin", remote.to_str().unwrap()]);
+  remote
+}
+
+#[test]
+fn create_mr_pushes_source_branch() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let repo = git_repo("push", "topic", &["Add topic"]);
+  let remote = bare_remote(&repo);
+  // the project has the branch at the first commit, so it is behind local
+  fake.add_branch("topic", &git(&repo, &["rev-parse", "master"]));
+  let options_log = repo.with_extension("options");
+
+  let run = |target: &str, extra: &[&str], stdin: &str| {
+    let mut args = vec![
+      "create", "mr", "-S", "topic", "-T", target, "-I", "Topic", "-o", "json",
+    ];
+    args.extend_from_slice(extra);
+    let mut child = command(&url, Some(TOKEN), &args)
+      .current_dir(&repo)
+      .env("PUSH_OPTIONS_LOG", &options_log)
+      .stdin(Stdio::piped())
+      .spawn()
+      .unwrap();
+    child
+      .stdin
+      .take()
+      .unwrap()
+      .write_all(stdin.as_bytes())
+      .unwrap();
+    child.wait_with_output().unwrap()
+  };
+
+  let out = run("master", &[], "y\ny\n");
+  let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+  assert!(stderr.contains("is behind local by 1 commit"), "{}", stderr);
+  assert_eq!(stdout_json(&out)["source_branch"], "topic");
+  assert_eq!(
+    git(&remote, &["rev-parse", "topic"]),
+    git(&repo, &["rev-parse", "topic"])
+  );
+
+  git(&repo, &["commit", "-q", "--allow-empty", "-m", "Fix topic"]);
+  // into another target branch, as the merge request into `master` is already open
+  let out = run(
+    "feature-1",
+    &["--create-on-push", "--push-option", "ci.skip"],
+    "y\n",
+  );
+  // a plain git remote does not create merge requests, so it is created with API
+  assert_eq!(stdout_json(&out)["title"], "Topic");
+  let options = fs::read_to_string(&options_log).unwrap();
+  assert!(options.contains("=ci.skip\n"), "{}", options);
+  assert!(options.contains("=merge_request.create\n"), "{}", options);
+  assert!(
+    options.contains("=merge_request.target=feature-1\n"),
+    "{}",
+    options
+  );
+}
+
+#[test]
+fn create_mr_rerun_pushes_new_commits() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let repo = git_repo("rerun", "topic", &["Add topic"]);
+  let remote = bare_remote(&repo);
+  fake.add_branch("topic", &git(&repo, &["rev-parse", "master"]));
+  let args = [
+    "create", "mr", "-S", "topic", "-T", "master", "-I", "Topic", "-o", "json",
+  ];
+  let run = |stdin: &str| {
+    let mut child = command(&url, Some(TOKEN), &args)
+      .current_dir(&repo)
+      .stdin(Stdio::piped())
+      .spawn()
+      .unwrap();
+    child
+      .stdin
+      .take()
+      .unwrap()
+      .write_all(stdin.as_bytes())
+      .unwrap();
+    child.wait_with_output().unwrap()
+  };
+
+  let created = stdout_json(&run("y\ny\n"));
+  let topic = git(&repo, &["rev-parse", "topic"]);
+  fake.add_branch("topic", &topic);
+
+  git(&repo, &["commit", "-q", "--allow-empty", "-m", "Fix topic"]);
+  // the open merge request already matches, only the new commit is pushed
+  let out = run("y\n");
+  assert!(
+    out.status.success(),
+    "{}",
+    String::from_utf8_lossy(&out.stderr)
+  );
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(stderr.contains("already open"), "{}", stderr);
+  assert!(stderr.contains("is behind local by 1 commit"), "{}", stderr);
+  assert_eq!(stdout_json(&out)["iid"], created["iid"]);
+  assert_eq!(
+    git(&remote, &["rev-parse", "topic"]),
+    git(&repo, &["rev-parse", "topic"])
+  );
+  let requests = fake.requests();
+  assert_eq!(requests.iter().filter(|r| r.method == "POST").count(), 1);
+  assert!(requests.iter().all(|r| r.method != "PUT"));
+}
+
+#[test]
+fn create_mr_updates_open_one() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "fix-login",
+    "-T",
+    "master",
+    "-I",
+    "Fix login form",
+    "-D",
+    "Details",
+    "-o",
+    "json",
+  ];
+  let out = mergereq(&url, TOKEN, &args, "n\n");
+  assert!(!out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(stderr.contains("!1 (Fix login)"), "{}", stderr);
+  assert!(stderr.contains("already open"), "{}", stderr);
+  assert!(
+    stderr.contains(&format!("{}/merge_requests/1", PROJECT)),
+    "{}",
+    stderr
+  );
+
+  let mr = stdout_json(&mergereq(&url, TOKEN, &args, "y\n"));
+  assert_eq!(mr["iid"], 1);
+  assert_eq!(mr["title"], "Fix login form");
+  assert_eq!(mr["description"], "Details");
+  assert!(fake.requests().iter().all(|r| r.method != "POST"));
+}
+
+#[test]
+fn create_mr_canceled() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "feature-3",
+    "-T",
+    "master",
+    "-I",
+    "Add feature",
+  ];
+  let out = mergereq(&url, TOKEN, &args, "n\n");
+  assert!(!out.status.success());
+  assert!(String::from_utf8_lossy(&out.stderr).contains("Canceling..."));
+  assert!(fake.requests().iter().all(|r| r.method != "POST"));
+}
+
+#[test]
+fn create_mr_validation_error() {
+  let url = FakeGitLab::new().serve();
+  let args = [
+    "create",
+    "mr",
+    "-S",
+    "no-such-branch",
+    "-T",
+    "master",
+    "-I",
+    "Title",
+  ];
+  let out = mergereq(&url, TOKEN, &args, "y\n");
+  assert!(!out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(
+    stderr.contains("[ERROR] Validation failed: source_branch is invalid"),
+    "{}",
+    stderr
+  );
+}
+
+#[test]
+fn wrong_token_is_reported() {
+  let url = FakeGitLab::new().serve();
+  let out = mergereq(&url, "wrong", &["ls", "mr", "-v"], "");
+  assert!(!out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(stderr.contains("[ERROR] Unauthorized"), "{}", stderr);
+  assert!(stderr.contains("[RESPONSE]"), "{}", stderr);
+}
+
+#[test]
+fn requests_are_logged_without_token() {
+  let url = FakeGitLab::new().serve();
+  let out = mergereq(&url, TOKEN, &["ls", "branches", "--limit", "1", "-vv"], "");
+  assert!(out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(
+    stderr.contains("private token is taken from command line"),
+    "{}",
+    stderr
+  );
+  assert!(stderr.contains("[INFO] GET "), "{}", stderr);
+  assert!(stderr.contains("-> 200 OK"), "{}", stderr);
+  assert!(!stderr.contains(TOKEN), "{}", stderr);
+
+  let log_file =
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("cli-log-{}.log", std::process::id()));
+  let log_path = log_file.to_str().unwrap();
+  let args = ["ls", "branches", "--debug", "--log-file", log_path];
+  let out = mergereq(&url, TOKEN, &args, "");
+  assert!(out.status.success());
+  assert!(out.stderr.is_empty());
+  let log = fs::read_to_string(&log_file).unwrap();
+  assert!(log.contains("[TRACE]"), "{}", log);
+  assert!(!log.contains(TOKEN), "{}", log);
+}
+
+#[test]
+fn record_and_replay() {
+  let url = FakeGitLab::new().serve();
+  let cassette = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+    .join(format!("cli-cassette-{}.json", std::process::id()));
+  let cassette = cassette.to_str().unwrap();
+
+  let args = ["ls", "branches", "-o", "csv", "--record", cassette];
+  let recorded = mergereq(&url, TOKEN, &args, "");
+  assert!(recorded.status.success());
+  assert!(!fs::read_to_string(cassette).unwrap().contains(TOKEN));
+
+  // GitLab is not reachable at this URL, responses come from cassette
+  let args = ["ls", "branches", "-o", "csv", "--replay", cassette];
+  let replayed = mergereq("http://127.0.0.1:9", "any", &args, "");
+  assert!(
+    replayed.status.success(),
+    "{}",
+    String::from_utf8_lossy(&replayed.stderr)
+  );
+  assert_eq!(replayed.stdout, recorded.stdout);
+}
+
+#[test]
+fn users_are_cached() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let args = ["ls", "users", "--username", "alice", "-o", "csv"];
+  let first = mergereq(&url, TOKEN, &args, "");
+  let second = mergereq(&url, TOKEN, &args, "");
+  assert_eq!(first.stdout, second.stdout);
+  // info of the token is cached as well
+  assert_eq!(fake.requests().len(), 2);
+
+  let args = ["ls", "users", "--username", "alice", "--no-cache"];
+  assert!(mergereq(&url, TOKEN, &args, "").status.success());
+  assert_eq!(fake.requests().len(), 4);
+
+  let cleared = stdout_json(&mergereq(
+    &url,
+    TOKEN,
+    &["cache", "clear", "-o", "json"],
+    "",
+  ));
+  assert_eq!(cleared["removed"], 2);
+}
+
+#[test]
+fn login_and_logout() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let args = [
+    "login",
+    "--client-id",
+    CLIENT_ID,
+    "--port",
+    "0",
+    "--store",
+    "plain",
+  ];
+  let mut login = command(&url, None, &args).spawn().unwrap();
+  // stderr is kept open, so that mergereq can write to it while waiting
+  let mut stderr = BufReader::new(login.stderr.take().unwrap()).lines();
+  let authorize_url = stderr
+    .by_ref()
+    .map(Result::unwrap)
+    .find(|l| l.contains("/oauth/authorize?"))
+    .expect("authorization page is not shown");
+  let authorize_url = Url::parse(&authorize_url).unwrap();
+  let param = |name: &str| {
+    authorize_url
+      .query_pairs()
+      .find(|(k, _)| k == name)
+      .map(|(_, v)| v.into_owned())
+      .unwrap()
+  };
+
+  // browser is redirected back after the user authorized mergereq
+  let redirect_uri = Url::parse(&param("redirect_uri")).unwrap();
+  let redirect = |state: &str| {
+    let mut url = redirect_uri.clone();
+    url
+      .query_pairs_mut()
+      .append_pair("code", AUTH_CODE)
+      .append_pair("state", state);
+    reqwest::get(url).unwrap().status()
+  };
+  // speculative connection of browser that never sends a request, and a stale page
+  let _preconnect = TcpStream::connect(("127.0.0.1", redirect_uri.port().unwrap())).unwrap();
+  assert_eq!(redirect("stale"), 400);
+  assert!(redirect(&param("state")).is_success());
+  assert!(login.wait().unwrap().success());
+  stderr.for_each(drop);
+
+  let users = command(&url, None, &["ls", "users", "-o", "json"])
+    .output()
+    .unwrap();
+  assert_eq!(stdout_json(&users).as_array().unwrap().len(), 3);
+
+  // expired access token is refreshed for lists, which are requested concurrently with `async`
+  fake.expire_access_tokens();
+  let branches = command(&url, None, &["ls", "branches", "-o", "json"])
+    .output()
+    .unwrap();
+  assert_eq!(
+    stdout_json(&branches).as_array().unwrap().len(),
+    BRANCHES_COUNT
+  );
+
+  let logout = command(&url, None, &["logout", "-o", "json"])
+    .output()
+    .unwrap();
+  assert_eq!(stdout_json(&logout)["revoked"], true);
+  let after = command(&url, None, &["ls", "users", "--no-cache"])
+    .output()
+    .unwrap();
+  assert!(!after.status.success());
+}
+
+#[test]
+fn any_host_token_is_used_for_explicit_url() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let mut cmd = command(&url, None, &["ls", "users", "-o", "json"]);
+  let global_cfg = format!("[hosts.\"*\"]\nprivate_token = \"{}\"\n", TOKEN);
+  fs::write(command_dir(&url).join("global.toml"), global_cfg).unwrap();
+  let out = cmd.output().unwrap();
+  assert_eq!(stdout_json(&out).as_array().unwrap().len(), 3);
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(stderr.contains("sent over plain HTTP"), "{}", stderr);
+}
+
+#[test]
+fn any_host_token_is_not_sent_to_inferred_url() {
+  let fake = FakeGitLab::new();
+  let url = fake.serve();
+  let repo = git_repo("github", "topic", &[]);
+  git(
+    &repo,
+    &[
+      "remote",
+      "add",
+      "origin",
+      &format!("git@github.com:{}.git", PROJECT),
+    ],
+  );
+  let global_cfg = repo.join("global.toml");
+  let local_cfg = repo.join("local.toml");
+  fs::write(&local_cfg, "").unwrap();
+  // GitHub remote is served by fake GitLab to see what is sent there
+  let ssh_hosts = format!("[ssh_hosts]\n\"github.com\" = \"{}\"\n", url);
+  let run = |hosts: &str| {
+    fs::write(&global_cfg, format!("{}{}", ssh_hosts, hosts)).unwrap();
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_mergereq"));
+    remove_settings_env(&mut cmd);
+    cmd
+      .current_dir(&repo)
+      .env("HOME", &repo)
+      .env("MERGEREQ_CACHE_DIR", repo.join("cache"))
+      .args(["ls", "users", "-o", "json", "-v"])
+      .args(["--global-config", global_cfg.to_str().unwrap()])
+      .args(["--local-config", local_cfg.to_str().unwrap()])
+      .output()
+      .unwrap()
+  };
+
+  let out = run(&format!("[hosts.\"*\"]\nprivate_token = \"{}\"\n", TOKEN));
+  assert!(!out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(
+    stderr.contains("inferred from git remote `origin`"),
+    "{}",
+    stderr
+  );
+  assert!(stderr.contains("the `*` token is not sent"), "{}", stderr);
+  assert!(fake.requests().is_empty());
+
+  let host = mergereq::configs::host_key(&url);
+  let out = run(&format!(
+    "[hosts.\"{}\"]\nprivate_token = \"{}\"\n",
+    host, TOKEN
+  ));
+  assert_eq!(stdout_json(&out).as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn whoami_warns_about_token() {
+  let fake = FakeGitLab::new();
+  fake.set_token_info(&["read_api"], Some(3));
+  let url = fake.serve();
+
+  let out = mergereq(&url, TOKEN, &["whoami", "-o", "json"], "");
+  let whoami = stdout_json(&out);
+  assert_eq!(whoami["user"]["username"], "admin");
+  assert_eq!(whoami["token"]["name"], "mergereq");
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(stderr.contains("expires in 3 days"), "{}", stderr);
+  assert!(stderr.contains("no `api` scope"), "{}", stderr);
+
+  // the check is not counted in request budget
+  let args = ["ls", "branches", "--limit", "1", "--request-budget", "1"];
+  let out = mergereq(&url, TOKEN, &args, "");
+  assert!(out.status.success());
+  let stderr = String::from_utf8_lossy(&out.stderr);
+  assert!(
+    stderr.contains("[WARN] Private token `mergereq` expires"),
+    "{}",
+    stderr
+  );
+
+  // failed check is neither retried nor stops the command
+  let sent = fake.requests().len();
+  fake.fail_next(1);
+  let out = mergereq(&url, TOKEN, &["ls", "branches", "--limit", "1"], "");
+  assert!(out.status.success());
+  assert_eq!(fake.requests().len(), sent + 2);
+}
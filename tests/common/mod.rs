@@ -0,0 +1,859 @@
+//! Fake GitLab for integration tests. It serves a few projects, users, branches
+//! and merge requests either in-process as `Transport` or over HTTP on a local port.
+
+#![allow(dead_code)]
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mergereq::api::{
+  GLApi, GLApiError, GLApiResult, HttpRequest, HttpResponse, OAuthClient, OAuthToken, ReqParams,
+  Transport,
+};
+use percent_encoding::percent_decode_str;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{StatusCode, Url};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const TOKEN: &str = "test-token";
+pub const PROJECT: &str = "group/project";
+pub const BRANCHES_COUNT: usize = 45;
+/// Fake caps `per_page` lower than GitLab, so that short lists have several pages
+pub const MAX_PER_PAGE: usize = 20;
+/// ID of OAuth application
+pub const CLIENT_ID: &str = "test-client";
+/// Authorization code that fake accepts from any redirect
+pub const AUTH_CODE: &str = "test-code";
+
+const PROJECTS: [&str; 3] = ["group/project", "group/other", "group/third"];
+
+/// Error of failed request, panics if request succeeded or failed not with `GLApiError`
+pub fn api_error<T>(res: GLApiResult<T>) -> GLApiError {
+  match res.map(|_| ()).map_err(|e| e.downcast::<GLApiError>()) {
+    Ok(()) => panic!("request is expected to fail"),
+    Err(Ok(e)) => e,
+    Err(Err(e)) => panic!("unexpected error: {}", e),
+  }
+}
+
+/// Request received by fake GitLab
+#[derive(Clone, Debug)]
+pub struct Recorded {
+  pub method: String,
+  /// Path with decoded project, e.g. `/projects/group/project/merge_requests`
+  pub path: String,
+  pub query: Vec<(String, String)>,
+  pub body: Option<Value>,
+  /// Status of reply
+  pub status: u16,
+}
+
+impl Recorded {
+  pub fn query_value(&self, name: &str) -> Option<&str> {
+    self
+      .query
+      .iter()
+      .find(|(k, _)| k == name)
+      .map(|(_, v)| v.as_str())
+  }
+}
+
+struct Reply {
+  status: u16,
+  headers: Vec<(String, String)>,
+  body: Value,
+}
+
+impl Reply {
+  fn json(status: u16, body: Value) -> Self {
+    Reply {
+      status,
+      headers: Vec::new(),
+      body,
+    }
+  }
+
+  fn message(status: u16, message: &str) -> Self {
+    Reply::json(status, json!({ "message": message }))
+  }
+
+  /// Body sent to client, empty for `304 Not Modified`
+  fn data(&self) -> String {
+    if self.status == 304 {
+      String::new()
+    } else {
+      self.body.to_string()
+    }
+  }
+}
+
+/// Weak ETag of response body, like GitLab sends for JSON responses
+fn etag(body: &Value) -> String {
+  let mut hasher = DefaultHasher::new();
+  body.to_string().hash(&mut hasher);
+  format!("W/\"{:016x}\"", hasher.finish())
+}
+
+struct State {
+  users: Vec<Value>,
+  branches: Vec<Value>,
+  mrs: Vec<Value>,
+  requests: Vec<Recorded>,
+  /// Count of the next requests answered with `502 Bad Gateway`
+  failures: u32,
+  /// Count of the next requests answered with `429 Too Many Requests`, and its `Retry-After`
+  rate_limited: (u32, u64),
+  /// Not revoked OAuth grants
+  grants: Vec<Grant>,
+  /// Count of issued OAuth grants
+  issued: u32,
+  /// Info of `TOKEN` served by `/personal_access_tokens/self`
+  token_info: Value,
+  /// Files of repository by path, the same in every branch
+  files: Vec<(String, String)>,
+}
+
+struct Grant {
+  access_token: String,
+  refresh_token: String,
+  /// Access token is not expired
+  active: bool,
+}
+
+#[derive(Clone)]
+pub struct FakeGitLab {
+  state: Arc<Mutex<State>>,
+}
+
+impl Default for FakeGitLab {
+  fn default() -> Self {
+    FakeGitLab::new()
+  }
+}
+
+impl FakeGitLab {
+  pub fn new() -> Self {
+    let users = vec![
+      user(1, "admin", "active"),
+      user(2, "alice", "active"),
+      user(3, "bob", "blocked"),
+    ];
+    let branches = (0..BRANCHES_COUNT).map(branch).collect();
+    let mrs = vec![
+      merge_request(1, "Fix login", "fix-login", "opened"),
+      merge_request(2, "Add dark theme", "dark-theme", "opened"),
+      merge_request(3, "Update deps", "update-deps", "merged"),
+    ];
+    let state = State {
+      users,
+      branches,
+      mrs,
+      requests: Vec::new(),
+      failures: 0,
+      rate_limited: (0, 0),
+      grants: Vec::new(),
+      issued: 0,
+      token_info: token_info(&["api"], None),
+      files: Vec::new(),
+    };
+    FakeGitLab {
+      state: Arc::new(Mutex::new(state)),
+    }
+  }
+
+  /// Client of this fake without network
+  pub fn api(&self) -> GLApi {
+    GLApi::with_transport(self.req_params(), self.clone())
+  }
+
+  pub fn req_params(&self) -> ReqParams {
+    ReqParams::new()
+      .repo_url("https://gitlab.fake")
+      .private_token(TOKEN)
+      .default_project(PROJECT)
+  }
+
+  /// Answers the next `count` requests with `502 Bad Gateway`
+  pub fn fail_next(&self, count: u32) {
+    self.state.lock().unwrap().failures = count;
+  }
+
+  /// Answers the next `count` requests with `429 Too Many Requests`,
+  /// asking to retry after `retry_after` seconds
+  pub fn rate_limit_next(&self, count: u32, retry_after: u64) {
+    self.state.lock().unwrap().rate_limited = (count, retry_after);
+  }
+
+  /// Sets scopes of `TOKEN` and count of days until it expires
+  pub fn set_token_info(&self, scopes: &[&str], expires_in_days: Option<i64>) {
+    self.state.lock().unwrap().token_info = token_info(scopes, expires_in_days);
+  }
+
+  /// Adds file to repositories of all projects
+  pub fn add_file(&self, path: &str, content: &str) {
+    let files = &mut self.state.lock().unwrap().files;
+    files.push((path.to_owned(), content.to_owned()));
+  }
+
+  /// Adds branch pointing to commit `sha`, replacing the branch with the same name
+  pub fn add_branch(&self, name: &str, sha: &str) {
+    let mut b = branch(BRANCHES_COUNT);
+    b["name"] = json!(name);
+    b["commit"]["id"] = json!(sha);
+    let mut state = self.state.lock().unwrap();
+    state.branches.retain(|old| old["name"] != name);
+    state.branches.push(b);
+  }
+
+  /// OAuth client of application registered in this fake
+  pub fn oauth_client(&self) -> OAuthClient {
+    OAuthClient::new("https://gitlab.fake", CLIENT_ID, self.clone())
+  }
+
+  /// Issues OAuth tokens as if the user logged in
+  pub fn login(&self) -> OAuthToken {
+    let reply = issue_grant(&mut self.state.lock().unwrap());
+    let body = &reply.body;
+    OAuthToken {
+      access_token: body["access_token"].as_str().unwrap().to_owned(),
+      refresh_token: body["refresh_token"].as_str().map(str::to_owned),
+      expires_at: None,
+      scope: None,
+    }
+  }
+
+  /// Makes access tokens rejected, while refresh tokens still can be used
+  pub fn expire_access_tokens(&self) {
+    for grant in &mut self.state.lock().unwrap().grants {
+      grant.active = false;
+    }
+  }
+
+  pub fn requests(&self) -> Vec<Recorded> {
+    self.state.lock().unwrap().requests.clone()
+  }
+
+  /// Starts HTTP server on a free local port and returns its URL
+  pub fn serve(&self) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind fake GitLab");
+    let addr = listener.local_addr().unwrap();
+    let fake = self.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let fake = fake.clone();
+        thread::spawn(move || fake.serve_connection(stream));
+      }
+    });
+    format!("http://{}", addr)
+  }
+
+  fn serve_connection(&self, stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    // connections are kept alive by clients, so several requests may come one by one
+    loop {
+      let mut request_line = String::new();
+      if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return;
+      }
+      let mut parts = request_line.split_whitespace();
+      let method = parts.next().unwrap_or_default().to_owned();
+      let target = parts.next().unwrap_or_default().to_owned();
+
+      let mut headers = Vec::new();
+      loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+          return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+          break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+          headers.push((name.trim().to_lowercase(), value.trim().to_owned()));
+        }
+      }
+      let header = |name: &str| {
+        headers
+          .iter()
+          .find(|(n, _)| n == name)
+          .map(|(_, v)| v.as_str())
+      };
+
+      let length: usize = header("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+      let mut body = vec![0; length];
+      if reader.read_exact(&mut body).is_err() {
+        return;
+      }
+
+      let host = header("host").unwrap_or("localhost");
+      let url = Url::parse(&format!("http://{}{}", host, target)).unwrap();
+      let body = if body.is_empty() { None } else { Some(body) };
+      let reply = self.handle(
+        &method,
+        &url,
+        request_token(header("private-token"), header("authorization")),
+        header("if-none-match"),
+        body.as_deref(),
+      );
+
+      let data = reply.data();
+      let status = StatusCode::from_u16(reply.status).unwrap();
+      let mut resp = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default(),
+        data.len()
+      );
+      for (name, value) in &reply.headers {
+        resp.push_str(&format!("{}: {}\r\n", name, value));
+      }
+      resp.push_str("\r\n");
+      resp.push_str(&data);
+      if writer.write_all(resp.as_bytes()).is_err() {
+        return;
+      }
+    }
+  }
+
+  /// Reply to request. Successful `GET` replies have `ETag`,
+  /// and `304 Not Modified` is sent if it matches `If-None-Match`
+  fn handle(
+    &self,
+    method: &str,
+    url: &Url,
+    token: Option<&str>,
+    if_none_match: Option<&str>,
+    body: Option<&[u8]>,
+  ) -> Reply {
+    let mut state = self.state.lock().unwrap();
+    let mut reply = route(&mut state, method, url, token, body);
+    if method == "GET" && reply.status == 200 {
+      let tag = etag(&reply.body);
+      if if_none_match == Some(tag.as_str()) {
+        reply.status = 304;
+      }
+      reply.headers.push(("ETag".to_owned(), tag));
+    }
+    if let Some(req) = state.requests.last_mut() {
+      req.status = reply.status;
+    }
+    reply
+  }
+}
+
+fn route(
+  state: &mut State,
+  method: &str,
+  url: &Url,
+  token: Option<&str>,
+  body: Option<&[u8]>,
+) -> Reply {
+  let segments: Vec<String> = url
+    .path_segments()
+    .map(|s| {
+      s.map(|seg| percent_decode_str(seg).decode_utf8_lossy().into_owned())
+        .collect()
+    })
+    .unwrap_or_default();
+  let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+  let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+  let form: Vec<(String, String)> = body
+    .and_then(|b| serde_urlencoded::from_bytes(b).ok())
+    .unwrap_or_default();
+  let body: Option<Value> = body.and_then(|b| serde_json::from_slice(b).ok());
+  state.requests.push(Recorded {
+    method: method.to_owned(),
+    path: format!("/{}", segments.get(2..).unwrap_or_default().join("/")),
+    query: query.clone(),
+    body: body.clone(),
+    status: 0,
+  });
+
+  if state.failures > 0 {
+    state.failures -= 1;
+    return Reply::message(502, "502 Bad Gateway");
+  }
+  if state.rate_limited.0 > 0 {
+    state.rate_limited.0 -= 1;
+    let mut reply = Reply::message(429, "Retry later");
+    let retry_after = state.rate_limited.1.to_string();
+    reply.headers.push(("Retry-After".to_owned(), retry_after));
+    return reply;
+  }
+  if let ["oauth", endpoint] = segments.as_slice() {
+    return oauth(state, endpoint, &form);
+  }
+  let oauth_token_valid = state
+    .grants
+    .iter()
+    .any(|g| g.active && token == Some(g.access_token.as_str()));
+  if token != Some(TOKEN) && !oauth_token_valid {
+    return Reply::message(401, "401 Unauthorized");
+  }
+  let param = |name: &str| {
+    query
+      .iter()
+      .find(|(k, _)| k == name)
+      .map(|(_, v)| v.as_str())
+  };
+
+  match (method, segments.as_slice()) {
+    ("GET", ["api", "v4", "user"]) => Reply::json(200, state.users[0].clone()),
+    ("GET", ["api", "v4", "personal_access_tokens", "self"]) if token == Some(TOKEN) => {
+      Reply::json(200, state.token_info.clone())
+    }
+    ("GET", ["api", "v4", "users"]) => {
+      let users: Vec<Value> = state
+        .users
+        .iter()
+        .filter(|u| param("username").is_none_or(|n| u["username"] == n))
+        .filter(|u| param("state").is_none_or(|s| u["state"] == s))
+        .cloned()
+        .collect();
+      offset_page(&users, url)
+    }
+    ("GET", ["api", "v4", "projects"]) => keyset_page(url),
+    ("GET", ["api", "v4", "projects", pr]) => match project(pr) {
+      Some(p) => Reply::json(200, p),
+      None => Reply::message(404, "404 Project Not Found"),
+    },
+    (_, ["api", "v4", "projects", pr, ..]) if project(pr).is_none() => {
+      Reply::message(404, "404 Project Not Found")
+    }
+    ("GET", ["api", "v4", "projects", _, "repository", "branches"]) => {
+      offset_page(&state.branches, url)
+    }
+    ("GET", ["api", "v4", "projects", _, "repository", "branches", name]) => {
+      match state.branches.iter().find(|b| b["name"] == *name) {
+        Some(b) => Reply::json(200, b.clone()),
+        None => Reply::message(404, "404 Branch Not Found"),
+      }
+    }
+    ("GET", ["api", "v4", "projects", _, "repository", "files", path]) => {
+      match state.files.iter().find(|(p, _)| p == path) {
+        Some((p, content)) => Reply::json(
+          200,
+          json!({
+            "file_name": p.rsplit('/').next(),
+            "file_path": p,
+            "size": content.len(),
+            "encoding": "base64",
+            "content": STANDARD.encode(content),
+            "ref": param("ref"),
+          }),
+        ),
+        None => Reply::message(404, "404 File Not Found"),
+      }
+    }
+    ("GET", ["api", "v4", "projects", _, "merge_requests"]) => {
+      let mrs: Vec<Value> = state
+        .mrs
+        .iter()
+        .filter(|mr| param("state").is_none_or(|s| s == "all" || mr["state"] == s))
+        .filter(|mr| param("source_branch").is_none_or(|b| mr["source_branch"] == b))
+        .filter(|mr| param("target_branch").is_none_or(|b| mr["target_branch"] == b))
+        .cloned()
+        .collect();
+      offset_page(&mrs, url)
+    }
+    ("POST", ["api", "v4", "projects", _, "merge_requests"]) => {
+      let body = body.unwrap_or_default();
+      if let Some(err) = validate_mr(&body, &state.branches) {
+        return err;
+      }
+      let iid = state.mrs.len() as u64 + 1;
+      let mut mr = merge_request(
+        iid,
+        body["title"].as_str().unwrap_or_default(),
+        body["source_branch"].as_str().unwrap_or_default(),
+        "opened",
+      );
+      mr["target_branch"] = body["target_branch"].clone();
+      mr["description"] = body["description"].clone();
+      state.mrs.push(mr.clone());
+      Reply::json(201, mr)
+    }
+    (_, ["api", "v4", "projects", _, "merge_requests", iid, rest @ ..]) => {
+      let iid: u64 = iid.parse().unwrap_or_default();
+      let pos = state.mrs.iter().position(|mr| mr["iid"] == iid);
+      let pos = match pos {
+        Some(p) => p,
+        None => return Reply::message(404, "404 Not found"),
+      };
+      match (method, rest) {
+        ("GET", []) => Reply::json(200, state.mrs[pos].clone()),
+        ("PUT", []) => {
+          let body = body.unwrap_or_default();
+          if body["title"] == "" {
+            return Reply::json(422, json!({ "message": { "title": ["can't be blank"] } }));
+          }
+          let mr = &mut state.mrs[pos];
+          for field in ["title", "description", "target_branch"] {
+            if !body[field].is_null() {
+              mr[field] = body[field].clone();
+            }
+          }
+          Reply::json(200, mr.clone())
+        }
+        ("GET", ["approvals"]) => Reply::json(
+          200,
+          json!({ "approvals_required": 1, "approvals_left": 1, "approved_by": [] }),
+        ),
+        _ => Reply::message(405, "405 Method Not Allowed"),
+      }
+    }
+    _ => Reply::json(404, json!({ "error": "404 Not Found" })),
+  }
+}
+
+impl Transport for FakeGitLab {
+  fn send(&self, req: &HttpRequest) -> GLApiResult<HttpResponse> {
+    let url = Url::parse(&req.url)?;
+    let header = |name: &str| req.headers.get(name).and_then(|h| h.to_str().ok());
+    let reply = self.handle(
+      req.method.as_str(),
+      &url,
+      request_token(header("private-token"), header("authorization")),
+      header("if-none-match"),
+      req.body.as_deref(),
+    );
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &reply.headers {
+      headers.insert(
+        HeaderName::from_bytes(name.as_bytes())?,
+        HeaderValue::from_str(value)?,
+      );
+    }
+    Ok(HttpResponse {
+      status: StatusCode::from_u16(reply.status)?,
+      headers,
+      body: reply.data(),
+    })
+  }
+}
+
+/// `PRIVATE-TOKEN` or OAuth access token from `Authorization: Bearer`
+fn request_token<'a>(
+  private_token: Option<&'a str>,
+  authorization: Option<&'a str>,
+) -> Option<&'a str> {
+  private_token.or_else(|| authorization.and_then(|a| a.strip_prefix("Bearer ")))
+}
+
+fn oauth_error(status: u16, error: &str) -> Reply {
+  Reply::json(
+    status,
+    json!({ "error": error, "error_description": format!("{} error", error) }),
+  )
+}
+
+fn issue_grant(state: &mut State) -> Reply {
+  state.issued += 1;
+  let grant = Grant {
+    access_token: format!("oauth-access-{}", state.issued),
+    refresh_token: format!("oauth-refresh-{}", state.issued),
+    active: true,
+  };
+  let created_at = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap()
+    .as_secs();
+  let reply = Reply::json(
+    200,
+    json!({
+      "access_token": grant.access_token,
+      "token_type": "Bearer",
+      "expires_in": 7200,
+      "refresh_token": grant.refresh_token,
+      "scope": "api",
+      "created_at": created_at,
+    }),
+  );
+  state.grants.push(grant);
+  reply
+}
+
+/// Token endpoint accepting `AUTH_CODE` and refresh tokens of grants, and revoke endpoint.
+/// Refresh token is replaced with a new one when used, like GitLab does
+fn oauth(state: &mut State, endpoint: &str, form: &[(String, String)]) -> Reply {
+  let field = |name: &str| {
+    form
+      .iter()
+      .find(|(k, _)| k == name)
+      .map(|(_, v)| v.as_str())
+  };
+  if field("client_id") != Some(CLIENT_ID) {
+    return oauth_error(401, "invalid_client");
+  }
+  match (endpoint, field("grant_type")) {
+    ("token", Some("authorization_code")) => {
+      if field("code") == Some(AUTH_CODE) && field("code_verifier").is_some() {
+        issue_grant(state)
+      } else {
+        oauth_error(400, "invalid_grant")
+      }
+    }
+    ("token", Some("refresh_token")) => {
+      let refresh_token = field("refresh_token");
+      match state
+        .grants
+        .iter()
+        .position(|g| Some(g.refresh_token.as_str()) == refresh_token)
+      {
+        Some(pos) => {
+          state.grants.remove(pos);
+          issue_grant(state)
+        }
+        None => oauth_error(400, "invalid_grant"),
+      }
+    }
+    ("revoke", _) => {
+      let token = field("token");
+      state.grants.retain(|g| {
+        Some(g.access_token.as_str()) != token && Some(g.refresh_token.as_str()) != token
+      });
+      Reply::json(200, json!({}))
+    }
+    _ => oauth_error(400, "unsupported_grant_type"),
+  }
+}
+
+fn validate_mr(body: &Value, branches: &[Value]) -> Option<Reply> {
+  let branch_exists = |field: &str| branches.iter().any(|b| b["name"] == body[field]);
+  let mut errors = serde_json::Map::new();
+  if body["title"].as_str().is_none_or(str::is_empty) {
+    errors.insert("title".to_owned(), json!(["can't be blank"]));
+  }
+  if !branch_exists("source_branch") {
+    errors.insert("source_branch".to_owned(), json!(["is invalid"]));
+  }
+  if !branch_exists("target_branch") {
+    errors.insert("target_branch".to_owned(), json!(["is invalid"]));
+  }
+  if errors.is_empty() {
+    None
+  } else {
+    Some(Reply::json(422, json!({ "message": errors })))
+  }
+}
+
+fn per_page(url: &Url) -> usize {
+  url
+    .query_pairs()
+    .find(|(k, _)| k == "per_page")
+    .and_then(|(_, v)| v.parse().ok())
+    .unwrap_or(MAX_PER_PAGE)
+    .min(MAX_PER_PAGE)
+}
+
+fn with_param(url: &Url, name: &str, value: &str) -> Url {
+  let mut next = url.clone();
+  let pairs: Vec<(String, String)> = url
+    .query_pairs()
+    .into_owned()
+    .filter(|(k, _)| k != name)
+    .collect();
+  {
+    let mut q = next.query_pairs_mut();
+    q.clear();
+    for (k, v) in &pairs {
+      q.append_pair(k, v);
+    }
+    q.append_pair(name, value);
+  }
+  next
+}
+
+/// Page of list with offset pagination headers, like GitLab sends them
+fn offset_page(items: &[Value], url: &Url) -> Reply {
+  let per_page = per_page(url);
+  let page: usize = url
+    .query_pairs()
+    .find(|(k, _)| k == "page")
+    .and_then(|(_, v)| v.parse().ok())
+    .unwrap_or(1)
+    .max(1);
+  let total_pages = items.len().div_ceil(per_page).max(1);
+  let chunk: Vec<Value> = items
+    .iter()
+    .skip((page - 1) * per_page)
+    .take(per_page)
+    .cloned()
+    .collect();
+
+  let mut headers = vec![
+    ("X-Total".to_owned(), items.len().to_string()),
+    ("X-Total-Pages".to_owned(), total_pages.to_string()),
+    ("X-Page".to_owned(), page.to_string()),
+    ("X-Per-Page".to_owned(), per_page.to_string()),
+  ];
+  if page < total_pages {
+    let next = (page + 1).to_string();
+    let link = with_param(url, "page", &next);
+    headers.push(("X-Next-Page".to_owned(), next));
+    headers.push(("Link".to_owned(), format!("<{}>; rel=\"next\"", link)));
+  } else {
+    headers.push(("X-Next-Page".to_owned(), String::new()));
+  }
+  Reply {
+    status: 200,
+    headers,
+    body: Value::Array(chunk),
+  }
+}
+
+/// Page of projects with keyset pagination by id, only `Link` header points to the next page
+fn keyset_page(url: &Url) -> Reply {
+  let per_page = per_page(url);
+  let id_before: usize = url
+    .query_pairs()
+    .find(|(k, _)| k == "id_before")
+    .and_then(|(_, v)| v.parse().ok())
+    .unwrap_or(usize::MAX);
+  let all: Vec<Value> = (1..=PROJECTS.len())
+    .rev()
+    .filter(|id| *id < id_before)
+    .filter_map(|id| project(PROJECTS[id - 1]))
+    .collect();
+  let chunk: Vec<Value> = all.iter().take(per_page).cloned().collect();
+
+  let mut headers = Vec::new();
+  if all.len() > per_page {
+    let last_id = chunk.last().unwrap()["id"].to_string();
+    let link = with_param(url, "id_before", &last_id);
+    headers.push(("Link".to_owned(), format!("<{}>; rel=\"next\"", link)));
+  }
+  Reply {
+    status: 200,
+    headers,
+    body: Value::Array(chunk),
+  }
+}
+
+fn project(path: &str) -> Option<Value> {
+  let id = PROJECTS.iter().position(|p| *p == path)? + 1;
+  let name = path.rsplit('/').next().unwrap_or_default();
+  Some(json!({
+    "id": id,
+    "description": null,
+    "default_branch": "master",
+    "ssh_url_to_repo": format!("git@gitlab.fake:{}.git", path),
+    "http_url_to_repo": format!("https://gitlab.fake/{}.git", path),
+    "web_url": format!("https://gitlab.fake/{}", path),
+    "readme_url": null,
+    "name": name,
+    "path": name,
+    "path_with_namespace": path,
+  }))
+}
+
+/// Personal access token of the first user
+fn token_info(scopes: &[&str], expires_in_days: Option<i64>) -> Value {
+  let today = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap()
+    .as_secs() as i64
+    / 86400;
+  json!({
+    "id": 1,
+    "name": "mergereq",
+    "revoked": false,
+    "active": true,
+    "scopes": scopes,
+    "user_id": 1,
+    "expires_at": expires_in_days.map(|d| date_from_days(today + d)),
+    "last_used_at": null,
+  })
+}
+
+/// `YYYY-MM-DD` date of day since Unix epoch (civil from days algorithm of Howard Hinnant)
+fn date_from_days(days: i64) -> String {
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z - era * 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let d = doy - (153 * mp + 2) / 5 + 1;
+  let m = if mp < 10 { mp + 3 } else { mp - 9 };
+  let y = yoe + era * 400 + i64::from(m <= 2);
+  format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+fn user(id: u32, username: &str, state: &str) -> Value {
+  json!({ "id": id, "username": username, "name": username.to_uppercase(), "state": state })
+}
+
+/// `master` and `feature-N` branches
+fn branch(i: usize) -> Value {
+  let name = if i == 0 {
+    "master".to_owned()
+  } else {
+    format!("feature-{}", i)
+  };
+  json!({
+    "name": name,
+    "merged": false,
+    "protected": i == 0,
+    "default": i == 0,
+    "developers_can_push": false,
+    "developers_can_merge": false,
+    "can_push": true,
+    "commit": {
+      "author_email": "admin@gitlab.fake",
+      "author_name": "Admin",
+      "authored_date": "2020-01-01T00:00:00Z",
+      "id": format!("{:040x}", i),
+      "short_id": format!("{:08x}", i),
+      "title": "Commit",
+      "message": "Commit",
+      "parent_ids": [],
+    },
+  })
+}
+
+fn merge_request(iid: u64, title: &str, source_branch: &str, state: &str) -> Value {
+  let admin = user(1, "admin", "active");
+  json!({
+    "id": 100 + iid,
+    "iid": iid,
+    "project_id": 1,
+    "title": title,
+    "description": null,
+    "state": state,
+    "merged_by": null,
+    "merged_at": null,
+    "closed_by": null,
+    "closed_at": null,
+    "created_at": "2020-01-01T00:00:00Z",
+    "updated_at": null,
+    "target_branch": "master",
+    "source_branch": source_branch,
+    "author": admin,
+    "assignee": null,
+    "assignees": [],
+    "source_project_id": 1,
+    "target_project_id": 1,
+    "labels": [],
+    "milestone": null,
+    "work_in_progress": false,
+    "merge_when_pipeline_succeeds": false,
+    "merge_status": "can_be_merged",
+    "sha": "abc",
+    "merge_commit_sha": null,
+    "should_remove_source_branch": null,
+    "force_remove_source_branch": false,
+    "squash": false,
+    "web_url": format!("https://gitlab.fake/{}/merge_requests/{}", PROJECT, iid),
+  })
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::{api_error, FakeGitLab, AUTH_CODE, PROJECT};
+use mergereq::api::{GLApi, GLApiError, OAuthSession, OAuthToken, Pkce};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Client authenticated with OAuth tokens, collecting tokens passed to refresh callback
+fn oauth_api(fake: &FakeGitLab, token: OAuthToken) -> (GLApi, Rc<RefCell<Vec<OAuthToken>>>) {
+  let refreshed = Rc::new(RefCell::new(Vec::new()));
+  let saved = refreshed.clone();
+  let session = OAuthSession::new(fake.oauth_client(), token).on_refresh(move |t| {
+    saved.borrow_mut().push(t.clone());
+    Ok(())
+  });
+  let params = fake.req_params().private_token("unused");
+  let api = GLApi::with_transport(params, fake.clone()).oauth(session);
+  (api, refreshed)
+}
+
+#[test]
+fn code_is_exchanged_for_tokens() {
+  let fake = FakeGitLab::new();
+  let client = fake.oauth_client();
+  let pkce = Pkce::new();
+  let url = client
+    .authorize_url("http://127.0.0.1:7171/callback", "api", "state", &pkce)
+    .unwrap();
+  let challenge = url
+    .query_pairs()
+    .find(|(k, _)| k == "code_challenge")
+    .map(|(_, v)| v.into_owned());
+  assert_eq!(challenge, Some(pkce.challenge.clone()));
+
+  let token = client
+    .exchange_code(AUTH_CODE, "http://127.0.0.1:7171/callback", &pkce)
+    .unwrap();
+  assert!(token.refresh_token.is_some());
+  assert!(!token.is_expired());
+
+  let err = client.exchange_code("wrong", "http://127.0.0.1:7171/callback", &pkce);
+  assert!(matches!(api_error(err), GLApiError::OAuth { .. }));
+}
+
+#[test]
+fn rejected_access_token_is_refreshed() {
+  let fake = FakeGitLab::new();
+  let (api, refreshed) = oauth_api(&fake, fake.login());
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  assert!(refreshed.borrow().is_empty());
+
+  fake.expire_access_tokens();
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  assert_eq!(refreshed.borrow().len(), 1);
+  // the new token is used for the next requests
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  assert_eq!(refreshed.borrow().len(), 1);
+}
+
+#[test]
+fn expired_access_token_is_refreshed_before_request() {
+  let fake = FakeGitLab::new();
+  let token = OAuthToken {
+    expires_at: Some(0),
+    ..fake.login()
+  };
+  let (api, refreshed) = oauth_api(&fake, token);
+  assert_eq!(api.get_project(PROJECT).unwrap().id, 1);
+  assert_eq!(refreshed.borrow().len(), 1);
+  assert_eq!(fake.requests().len(), 2);
+}
+
+#[test]
+fn revoked_token_cannot_be_refreshed() {
+  let fake = FakeGitLab::new();
+  let token = fake.login();
+  fake.oauth_client().revoke(&token.access_token).unwrap();
+
+  let (api, _) = oauth_api(&fake, token);
+  match api_error(api.get_project(PROJECT)) {
+    GLApiError::OAuth { error, .. } => assert_eq!(error, "invalid_grant"),
+    e => panic!("unexpected error: {}", e),
+  }
+}